@@ -0,0 +1,45 @@
+// Times the lexer and a full compile on generated programs of growing size. Both are
+// linear when the time per line stays flat as the program grows. Run with `cargo bench`.
+
+use rust_basic_compiler::{compile, tokenize, CompileOptions};
+use std::time::Instant;
+
+fn program(lines: usize) -> String {
+    let mut source = String::from("LET total = 0\n");
+    for i in 0..lines {
+        source.push_str(&format!(
+            "LET v{} = total * {} + 12 - total / 3 # running value\n",
+            i % 100,
+            i
+        ));
+        source.push_str(&format!("LET total = total + v{}\n", i % 100));
+    }
+    source.push_str("PRINT \"total: \"\nPRINT total\n");
+    source
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>10} {:>12} {:>10} {:>14} {:>12}",
+        "lines", "tokens", "lex (ms)", "ns/line", "compile (ms)", "ns/line"
+    );
+    for lines in [12_500, 25_000, 50_000, 100_000] {
+        let source = program(lines);
+        let line_count = source.lines().count();
+        let start = Instant::now();
+        let tokens = tokenize(&source).expect("generated program lexes");
+        let lexed = start.elapsed();
+        let start = Instant::now();
+        compile(&source, &CompileOptions::default()).expect("generated program compiles");
+        let compiled = start.elapsed();
+        println!(
+            "{:>8} {:>10} {:>12.2} {:>10.0} {:>14.2} {:>12.0}",
+            line_count,
+            tokens.len(),
+            lexed.as_secs_f64() * 1000.0,
+            lexed.as_nanos() as f64 / line_count as f64,
+            compiled.as_secs_f64() * 1000.0,
+            compiled.as_nanos() as f64 / line_count as f64
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use crate::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Print {
+        value: PrintValue,
+        span: Span,
+    },
+    If {
+        condition: Condition,
+        body: Vec<Stmt>,
+        // The ELSEIF branches in order, each tried when all before it were false.
+        elseifs: Vec<(Condition, Vec<Stmt>)>,
+        else_body: Option<Vec<Stmt>>,
+        span: Span,
+    },
+    While {
+        condition: Condition,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    // FOR name = start TO end [STEP step] ... NEXT [name]. The end and step are evaluated
+    // once, after the variable is set to the start; a negative step counts down.
+    For {
+        name: String,
+        start: Expr,
+        end: Expr,
+        // Without STEP the variable goes up by 1.
+        step: Option<Expr>,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    Label {
+        name: String,
+        span: Span,
+    },
+    Goto {
+        name: String,
+        span: Span,
+    },
+    Let {
+        name: String,
+        value: Expr,
+        span: Span,
+    },
+    Input {
+        name: String,
+        span: Span,
+    },
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Print { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::For { span, .. }
+            | Stmt::Label { span, .. }
+            | Stmt::Goto { span, .. }
+            | Stmt::Let { span, .. }
+            | Stmt::Input { span, .. } => *span,
+        }
+    }
+
+    // The nested statement lists of a block statement, in source order.
+    pub fn blocks(&self) -> Vec<&[Stmt]> {
+        match self {
+            Stmt::If {
+                body,
+                elseifs,
+                else_body,
+                ..
+            } => {
+                let mut blocks = vec![body.as_slice()];
+                blocks.extend(elseifs.iter().map(|(_, body)| body.as_slice()));
+                blocks.extend(else_body.as_deref());
+                blocks
+            }
+            Stmt::While { body, .. } | Stmt::For { body, .. } => vec![body],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// PRINT takes either a string literal or an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintValue {
+    // The decoded string, printed exactly as is.
+    String(String),
+    Expr(Expr),
+}
+
+// The condition of an IF or WHILE.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    // A single comparison; comparisons do not chain.
+    Compare {
+        op: CompareOp,
+        left: Expr,
+        right: Expr,
+        span: Span,
+    },
+    Not {
+        operand: Box<Condition>,
+        span: Span,
+    },
+    // AND and OR, which only evaluate the right side when the left does not decide.
+    Logical {
+        op: LogicalOp,
+        left: Box<Condition>,
+        right: Box<Condition>,
+        span: Span,
+    },
+    // A condition in parentheses.
+    Group {
+        inner: Box<Condition>,
+        span: Span,
+    },
+}
+
+impl Condition {
+    pub fn span(&self) -> Span {
+        match self {
+            Condition::Compare { span, .. }
+            | Condition::Not { span, .. }
+            | Condition::Logical { span, .. }
+            | Condition::Group { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+        span: Span,
+    },
+    // An expression in parentheses.
+    Group {
+        inner: Box<Expr>,
+        span: Span,
+    },
+    // The literal spelled for C: a decimal integer, or a double with `.` or `e`.
+    Number {
+        text: String,
+        span: Span,
+    },
+    Var {
+        name: String,
+        span: Span,
+    },
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Group { span, .. }
+            | Expr::Number { span, .. }
+            | Expr::Var { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    // `%` or MOD, the remainder of a division truncated toward zero, as C's fmod.
+    Mod,
+    // `\`, division truncated toward zero.
+    IntDiv,
+    // `^`, which binds more tightly than a unary sign, so `-2 ^ 2` is -4.
+    Pow,
+}
+
+impl BinaryOp {
+    // Operators with a higher precedence bind more tightly; all but Pow are
+    // left-associative.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Sub => 1,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod | BinaryOp::IntDiv => 2,
+            BinaryOp::Pow => 3,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::IntDiv => "\\",
+            BinaryOp::Pow => "^",
+        }
+    }
+
+    // C has no operator for these, so they are calls into the maths library.
+    pub fn is_call(&self) -> bool {
+        matches!(self, BinaryOp::Mod | BinaryOp::IntDiv | BinaryOp::Pow)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+}
+
+impl UnaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Plus => "+",
+            UnaryOp::Minus => "-",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl CompareOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::NotEq => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::LtEq => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::GtEq => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+impl LogicalOp {
+    // AND binds more tightly than OR, as `&&` does over `||` in C.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            LogicalOp::Or => 1,
+            LogicalOp::And => 2,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LogicalOp::And => "&&",
+            LogicalOp::Or => "||",
+        }
+    }
+}
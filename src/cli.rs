@@ -0,0 +1,210 @@
+// Command-line argument parsing for the compiler binary.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+pub const USAGE: &str = "\
+Usage: rust-basic-compiler [COMMAND] [OPTIONS] <FILE>
+       rust-basic-compiler repl [--ignore-keyword-case]
+
+Compile a Teeny Tiny program to C.
+
+Commands:
+  compile    Translate the program to C (the default)
+  build      Compile the program to a native executable with the system C compiler
+  run        Build the program in a temporary directory and run it
+  interpret  Run the program with the built-in interpreter, without a C compiler
+  repl       Run statements interactively as they are typed
+
+Arguments:
+  <FILE>  Source file to compile, or `-` to read standard input
+
+Options:
+  -o, --output <PATH>  Where to write the result, or `-` for standard output
+                       [default: out.c for --emit=c, standard output otherwise;
+                       for build, the file name without its extension]
+      --emit <KIND>    What to produce: tokens, ast or c [default: c]
+      --cc <PATH>      C compiler for build and run [default: $CC, or cc]
+      --cflag <FLAG>   Extra C compiler flag, may be repeated [default: $CFLAGS]
+      --ignore-keyword-case
+                       Accept keywords in any letter case, like `print` or `Let`
+  -q, --quiet          Print nothing but diagnostics
+  -h, --help           Print this help
+  -V, --version        Print the version
+";
+
+// Exit statuses.
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_COMPILE_ERROR: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_IO_ERROR: i32 = 3;
+pub const EXIT_RUNTIME_ERROR: i32 = 4;
+
+// The exit status for `run` to pass on from the program it ran: the program's own, or 128
+// plus the signal that killed it, as shells report it, so a crash is not mistaken for a
+// compile error.
+pub fn program_exit_status(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    EXIT_RUNTIME_ERROR
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    C,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    // Source path, `-` for standard input.
+    pub input: String,
+    // Output path, `-` for standard output. None picks the default for `emit`.
+    pub output: Option<String>,
+    pub emit: Emit,
+    pub quiet: bool,
+    // Overrides for the C compiler from the environment.
+    pub cc: Option<String>,
+    pub cflags: Vec<String>,
+    pub ignore_keyword_case: bool,
+}
+
+impl Args {
+    // Where `compile` writes: C goes to out.c by default, the inspection formats to the
+    // terminal.
+    pub fn compile_output(&self) -> &str {
+        match (&self.output, self.emit) {
+            (Some(output), _) => output.as_str(),
+            (None, Emit::C) => "out.c",
+            (None, _) => "-",
+        }
+    }
+
+    // Where `build` puts the executable: by default in the current directory, named after
+    // the source file.
+    pub fn build_output(&self) -> PathBuf {
+        if let Some(output) = &self.output {
+            return PathBuf::from(output);
+        }
+        let path = Path::new(&self.input);
+        match path.file_stem() {
+            Some(stem) if self.input != "-" && Some(stem) != path.file_name() => {
+                PathBuf::from(stem)
+            }
+            Some(stem) if self.input != "-" => PathBuf::from(stem).with_extension("out"),
+            _ => PathBuf::from("a.out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Compile(Args),
+    Build(Args),
+    Run(Args),
+    Interpret(Args),
+    Repl { ignore_keyword_case: bool },
+    Help,
+    Version,
+}
+
+// Parse the arguments after the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.next_if(|arg| arg == "repl").is_some() {
+        let mut ignore_keyword_case = false;
+        for arg in args {
+            match arg.as_str() {
+                "--ignore-keyword-case" => ignore_keyword_case = true,
+                _ => return Err(format!("unexpected argument {:?}", arg)),
+            }
+        }
+        return Ok(Command::Repl {
+            ignore_keyword_case,
+        });
+    }
+    // An optional command comes first; without one we compile.
+    let command: fn(Args) -> Command = match args
+        .next_if(|arg| matches!(arg.as_str(), "compile" | "build" | "run" | "interpret"))
+        .as_deref()
+    {
+        Some("build") => Command::Build,
+        Some("run") => Command::Run,
+        Some("interpret") => Command::Interpret,
+        _ => Command::Compile,
+    };
+    let mut input = None;
+    let mut output = None;
+    let mut emit = Emit::C;
+    let mut quiet = false;
+    let mut cc = None;
+    let mut cflags = Vec::new();
+    let mut ignore_keyword_case = false;
+
+    while let Some(arg) = args.next() {
+        // Long options may carry their value inline, as in `--emit=c`.
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| -> Result<String, String> {
+            match inline_value {
+                Some(value) => Ok(value.to_string()),
+                None => args.next().ok_or_else(|| format!("{} needs a value", name)),
+            }
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-q" | "--quiet" => quiet = true,
+            "--ignore-keyword-case" => ignore_keyword_case = true,
+            "-o" | "--output" => output = Some(value(&name)?),
+            "--cc" => cc = Some(value(&name)?),
+            "--cflag" => cflags.push(value(&name)?),
+            "--emit" => {
+                emit = match value(&name)?.as_str() {
+                    "tokens" => Emit::Tokens,
+                    "ast" => Emit::Ast,
+                    "c" => Emit::C,
+                    other => {
+                        return Err(format!(
+                            "unknown --emit kind {:?}, expected tokens, ast or c",
+                            other
+                        ))
+                    }
+                }
+            }
+            _ if name.starts_with('-') && name != "-" => {
+                return Err(format!("unknown option {:?}", arg))
+            }
+            _ => {
+                if input.is_some() {
+                    return Err(format!("unexpected argument {:?}", arg));
+                }
+                input = Some(arg);
+            }
+        }
+    }
+
+    match input {
+        Some(input) => Ok(command(Args {
+            input,
+            output,
+            emit,
+            quiet,
+            cc,
+            cflags,
+            ignore_keyword_case,
+        })),
+        None => Err("no input file given".to_string()),
+    }
+}
@@ -0,0 +1,347 @@
+use crate::ast::*;
+use crate::mangle;
+use crate::Emitter;
+
+// Walks the AST and writes the equivalent C program into an Emitter.
+#[derive(Debug)]
+pub struct CodeGen<'a> {
+    pub emitter: &'a mut Emitter,
+    // Variables already given a `float` declaration in the header.
+    pub declared: Vec<String>,
+    // Emit `#line` directives so C compiler messages point at the Teeny Tiny source.
+    pub line_directives: bool,
+    // The name the `#line` directives give the Teeny Tiny source.
+    pub source_name: String,
+    // Set once the generated code uses a function from the maths library.
+    pub needs_libm: bool,
+    // FOR loops generated so far, numbering the temporaries that hold each one's end and step.
+    pub loops: usize,
+}
+
+impl<'a> CodeGen<'a> {
+    pub fn new(emitter: &'a mut Emitter) -> CodeGen<'a> {
+        CodeGen {
+            emitter,
+            declared: Vec::new(),
+            line_directives: true,
+            source_name: "<source>".to_string(),
+            needs_libm: false,
+            loops: 0,
+        }
+    }
+
+    pub fn program(&mut self, program: &Program) {
+        for stmt in program.statements.iter() {
+            self.statement(stmt);
+        }
+        // Whether <math.h> is needed is only known after generating every statement, so put
+        // the includes in front of the declarations made so far.
+        let declarations = std::mem::take(&mut self.emitter.header);
+        self.emitter.header_line("#include <stdio.h>".to_string());
+        if self.needs_libm {
+            self.emitter.header_line("#include <math.h>".to_string());
+        }
+        self.emitter.header_line("int main(void){".to_string());
+        self.emitter.header.push_str(&declarations);
+        self.emitter.emit_line("return 0;".to_string());
+        self.emitter.emit_line("}".to_string());
+    }
+
+    // Declare `name` in the header the first time it is assigned, by the statement at `line`.
+    fn declare(&mut self, name: &str, line: usize) {
+        if !self.declared.iter().any(|declared| declared == name) {
+            self.declared.push(name.to_string());
+            if self.line_directives {
+                self.emitter.header_line(self.line_directive(line));
+            }
+            self.emitter.header_line(format!("float {};", mangle(name)));
+        }
+    }
+
+    // Naming the file as well as the line keeps C compiler messages about other files,
+    // such as a header, from being read as messages about the Teeny Tiny source.
+    fn line_directive(&self, line: usize) -> String {
+        format!("#line {} {}", line, c_string(&self.source_name))
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        // Point the C compiler back at the Teeny Tiny line this statement came from.
+        if self.line_directives {
+            self.emitter
+                .emit_line(self.line_directive(stmt.span().line));
+        }
+        match stmt {
+            Stmt::Print { value, .. } => match value {
+                PrintValue::String(text) => {
+                    self.emitter
+                        .emit_line(format!("printf(\"%s\", {});", c_string(text)));
+                }
+                PrintValue::Expr(expr) => {
+                    self.emitter
+                        .emit(format!("{}{}", "printf(\"%", ".2f\\n\", (float)("));
+                    self.expression(expr);
+                    self.emitter.emit_line("));".to_string());
+                }
+            },
+            Stmt::If {
+                condition,
+                body,
+                elseifs,
+                else_body,
+                ..
+            } => {
+                self.emitter.emit("if(".to_string());
+                self.condition(condition);
+                self.emitter.emit_line("){".to_string());
+                for stmt in body.iter() {
+                    self.statement(stmt);
+                }
+                for (condition, body) in elseifs.iter() {
+                    self.emitter.emit("}else if(".to_string());
+                    self.condition(condition);
+                    self.emitter.emit_line("){".to_string());
+                    for stmt in body.iter() {
+                        self.statement(stmt);
+                    }
+                }
+                if let Some(body) = else_body {
+                    self.emitter.emit_line("}else{".to_string());
+                    for stmt in body.iter() {
+                        self.statement(stmt);
+                    }
+                }
+                self.emitter.emit_line("}".to_string());
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.emitter.emit("while(".to_string());
+                self.condition(condition);
+                self.emitter.emit_line("){".to_string());
+                for stmt in body.iter() {
+                    self.statement(stmt);
+                }
+                self.emitter.emit_line("}".to_string());
+            }
+            // The temporaries are declared at the top of main, where a GOTO into the body
+            // cannot skip them, and have no `tt_` prefix, so they never clash with names
+            // from the program.
+            Stmt::For {
+                name,
+                start,
+                end,
+                step,
+                body,
+                span,
+            } => {
+                self.declare(name, span.line);
+                self.loops += 1;
+                let name = mangle(name);
+                let end_name = format!("for{}_end", self.loops);
+                self.emitter.header_line(format!("float {} = 0;", end_name));
+                self.emitter.emit(format!("for({} = ", name));
+                self.expression(start);
+                self.emitter.emit(format!(", {} = ", end_name));
+                self.expression(end);
+                match step {
+                    Some(step) => {
+                        let step_name = format!("for{}_step", self.loops);
+                        self.emitter
+                            .header_line(format!("float {} = 1;", step_name));
+                        self.emitter.emit(format!(", {} = ", step_name));
+                        self.expression(step);
+                        self.emitter.emit_line(format!(
+                            "; {step}<0 ? {name}>={end} : {name}<={end}; {name} += {step}){{",
+                            step = step_name,
+                            name = name,
+                            end = end_name,
+                        ));
+                    }
+                    None => self.emitter.emit_line(format!(
+                        "; {name}<={end}; {name} += 1){{",
+                        name = name,
+                        end = end_name,
+                    )),
+                }
+                for stmt in body.iter() {
+                    self.statement(stmt);
+                }
+                self.emitter.emit_line("}".to_string());
+            }
+            Stmt::Label { name, .. } => {
+                self.emitter.emit_line(format!("{}:", mangle(name)));
+            }
+            Stmt::Goto { name, .. } => {
+                self.emitter.emit_line(format!("goto {};", mangle(name)));
+            }
+            Stmt::Let { name, value, span } => {
+                self.declare(name, span.line);
+                self.emitter.emit(format!("{} = ", mangle(name)));
+                self.expression(value);
+                self.emitter.emit_line(";".to_string());
+            }
+            Stmt::Input { name, span } => {
+                self.declare(name, span.line);
+                let name = mangle(name);
+                self.emitter
+                    .emit_line(format!("if(0 == scanf(\"%f\", &{})) {{", name));
+                self.emitter.emit_line(format!("{} = 0;", name));
+                self.emitter.emit("scanf(\"%".to_string());
+                self.emitter.emit_line("*s\");".to_string());
+                self.emitter.emit_line("}".to_string());
+            }
+        }
+    }
+
+    // Comparisons bind more tightly than `&&` and `||` in C, as in Teeny Tiny, so only
+    // groups, NOT operands and an OR inside an AND need parentheses.
+    fn condition(&mut self, condition: &Condition) {
+        match condition {
+            Condition::Compare {
+                op, left, right, ..
+            } => {
+                self.expression(left);
+                self.emitter.emit(op.symbol().to_string());
+                self.expression(right);
+            }
+            Condition::Not { operand, .. } => {
+                self.emitter.emit("!".to_string());
+                let parens = !matches!(**operand, Condition::Group { .. } | Condition::Not { .. });
+                self.condition_operand(operand, parens);
+            }
+            Condition::Logical {
+                op, left, right, ..
+            } => {
+                let parens = |operand: &Condition| matches!(*operand, Condition::Logical { op: inner, .. } if inner.precedence() < op.precedence());
+                self.condition_operand(left, parens(left));
+                self.emitter.emit(op.symbol().to_string());
+                self.condition_operand(right, parens(right));
+            }
+            Condition::Group { inner, .. } => self.condition_operand(inner, true),
+        }
+    }
+
+    fn condition_operand(&mut self, condition: &Condition, parens: bool) {
+        if parens {
+            self.emitter.emit("(".to_string());
+            self.condition(condition);
+            self.emitter.emit(")".to_string());
+        } else {
+            self.condition(condition);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            // Parenthesize the operands where C would otherwise group them differently, or
+            // where a unary sign after another operator would read as `--` or `++`.
+            // A call is a whole C expression of its own, so it never needs them.
+            Expr::Binary {
+                op: BinaryOp::Mod,
+                left,
+                right,
+                ..
+            } => self.call("fmod", left, right),
+            Expr::Binary {
+                op: BinaryOp::Pow,
+                left,
+                right,
+                ..
+            } => self.call("pow", left, right),
+            // Divide as doubles, whatever the operand types, then drop the fraction.
+            Expr::Binary {
+                op: BinaryOp::IntDiv,
+                left,
+                right,
+                ..
+            } => {
+                self.needs_libm = true;
+                self.emitter.emit("trunc((double)".to_string());
+                self.operand(left, c_operator(left));
+                self.emitter.emit("/".to_string());
+                self.operand(right, c_operator(right) || is_unary(right));
+                self.emitter.emit(")".to_string());
+            }
+            Expr::Binary {
+                op, left, right, ..
+            } => {
+                let left_parens = matches!(**left, Expr::Binary { op: inner, .. } if !inner.is_call() && inner.precedence() < op.precedence());
+                let right_parens = match **right {
+                    Expr::Binary { op: inner, .. } => {
+                        !inner.is_call() && inner.precedence() <= op.precedence()
+                    }
+                    Expr::Unary { .. } => true,
+                    _ => false,
+                };
+                self.operand(left, left_parens);
+                self.emitter.emit(op.symbol().to_string());
+                self.operand(right, right_parens);
+            }
+            Expr::Unary { op, operand, .. } => {
+                let parens = c_operator(operand) || is_unary(operand);
+                self.emitter.emit(op.symbol().to_string());
+                self.operand(operand, parens);
+            }
+            Expr::Group { inner, .. } => self.operand(inner, true),
+            Expr::Number { text, .. } => self.emitter.emit(text.clone()),
+            Expr::Var { name, .. } => self.emitter.emit(mangle(name)),
+        }
+    }
+
+    // A call into the maths library with the two operands as its arguments.
+    fn call(&mut self, function: &str, left: &Expr, right: &Expr) {
+        self.needs_libm = true;
+        self.emitter.emit(format!("{}(", function));
+        self.expression(left);
+        self.emitter.emit(",".to_string());
+        self.expression(right);
+        self.emitter.emit(")".to_string());
+    }
+
+    fn operand(&mut self, expr: &Expr, parens: bool) {
+        if parens {
+            self.emitter.emit("(".to_string());
+            self.expression(expr);
+            self.emitter.emit(")".to_string());
+        } else {
+            self.expression(expr);
+        }
+    }
+}
+
+// Whether `expr` is emitted as a binary C operator, rather than a call or an operand.
+fn c_operator(expr: &Expr) -> bool {
+    matches!(expr, Expr::Binary { op, .. } if !op.is_call())
+}
+
+fn is_unary(expr: &Expr) -> bool {
+    matches!(expr, Expr::Unary { .. })
+}
+
+// A C string literal with the value of `text`. Characters outside ASCII are written as
+// octal escapes of their UTF-8 bytes, so the literal does not depend on the C compiler's
+// source character set.
+fn c_string(text: &str) -> String {
+    let mut literal = String::from("\"");
+    let mut prev = '\0';
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            // Two question marks could start a trigraph.
+            '?' if prev == '?' => literal.push_str("\\?"),
+            _ if !c.is_ascii() => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    literal.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+            _ => literal.push(c),
+        }
+        prev = c;
+    }
+    literal.push('"');
+    literal
+}
@@ -1,11 +1,9 @@
-use std::fs::{self, File};
+use std::fs;
 use std::io;
-use std::io::Write;
 
 #[derive(Debug)]
 pub struct Emitter {
     pub output_filename: String,
-    pub full_path: String,
     pub header: String,
     pub code: String,
 }
@@ -13,23 +11,48 @@ impl Emitter {
     pub fn new(output_filename: String) -> Emitter {
         Emitter {
             output_filename,
-            full_path: String::new(),
             header: String::new(),
             code: String::new(),
         }
     }
+    // Append in place, so generating a program takes time linear in its size.
     pub fn emit(&mut self, code: String) {
-        self.code = format!("{}{}", self.code, code);
+        self.code.push_str(&code);
     }
     pub fn emit_line(&mut self, code: String) {
-        self.code = format!("{}{}\n", self.code, code);
+        self.code.push_str(&code);
+        self.code.push('\n');
     }
     pub fn header_line(&mut self, code: String) {
-        self.header = format!("{}{}\n", self.header, code);
+        self.header.push_str(&code);
+        self.header.push('\n');
+    }
+    // The complete C source: header followed by code.
+    pub fn output(&self) -> String {
+        format!("{}{}", self.header, self.code)
     }
     pub fn write_file(&self) -> io::Result<()> {
-        let path = &self.output_filename;
-        fs::write(path, format!("{}{}", self.header, self.code)).expect("Unable to write file");
-        Ok(())
+        fs::write(&self.output_filename, self.output())
+    }
+}
+
+// The C name for a Teeny Tiny variable or label. Every name gets a `tt_` prefix so it
+// cannot collide with C keywords or with `main`, `printf` and the rest of the C library.
+// After it, ASCII letters and digits are kept, `_` is doubled and any other character
+// becomes `_u` and four hex digits, or `_U` and eight, like a C universal character name;
+// so different names always mangle differently.
+pub fn mangle(name: &str) -> String {
+    let mut mangled = String::from("tt_");
+    for c in name.chars() {
+        if c == '_' {
+            mangled.push_str("__");
+        } else if c.is_ascii() {
+            mangled.push(c);
+        } else if (c as u32) <= 0xFFFF {
+            mangled.push_str(&format!("_u{:04x}", c as u32));
+        } else {
+            mangled.push_str(&format!("_U{:08x}", c as u32));
+        }
     }
+    mangled
 }
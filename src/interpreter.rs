@@ -0,0 +1,601 @@
+use crate::ast::*;
+use crate::Diagnostic;
+use crate::Span;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+// A C value. The generated C mixes `int` literals, `double` literals and `float`
+// variables, and the usual arithmetic conversions between them decide each result,
+// so the interpreter tracks the same three types.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    // A 32-bit `int`, which wraps on overflow as it does with the usual C compilers.
+    Int(i32),
+    Float(f32),
+    Double(f64),
+}
+
+impl Value {
+    fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(value) => value as f64,
+            Value::Float(value) => value as f64,
+            Value::Double(value) => value,
+        }
+    }
+
+    fn as_f32(self) -> f32 {
+        match self {
+            Value::Int(value) => value as f32,
+            Value::Float(value) => value,
+            Value::Double(value) => value as f32,
+        }
+    }
+}
+
+// Where a label lives: the (statement, block) pairs to enter from the top level, then the
+// label's index in the innermost block.
+#[derive(Debug, Clone)]
+struct Location {
+    enter: Vec<(usize, usize)>,
+    index: usize,
+}
+
+enum Flow {
+    Normal,
+    Goto(String),
+}
+
+enum Scan {
+    Value(f32),
+    NoMatch,
+    Eof,
+}
+
+// Executes a program directly, with the semantics of the C the code generator emits.
+// INPUT reads from `input` the way `scanf("%f")` would, PRINT writes to `output`.
+#[derive(Debug)]
+pub struct Interpreter<R: BufRead, W: Write> {
+    pub input: R,
+    pub output: W,
+    pub variables: HashMap<String, f32>,
+    // The end and step of each FOR loop that has started, by the offset of its statement.
+    // Like the C temporaries, they outlive the loop, and a GOTO into a loop that never
+    // started finds an end of 0 and a step of 1.
+    pub loops: HashMap<usize, (f32, f32)>,
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub fn new(input: R, output: W) -> Interpreter<R, W> {
+        Interpreter {
+            input,
+            output,
+            variables: HashMap::new(),
+            loops: HashMap::new(),
+        }
+    }
+
+    pub fn run(&mut self, program: &Program) -> Result<(), Diagnostic> {
+        let mut labels = HashMap::new();
+        Self::locate_labels(&program.statements, &mut Vec::new(), &mut labels);
+
+        let mut flow = self.block(&program.statements, 0)?;
+        while let Flow::Goto(label) = flow {
+            flow = self.resume(&program.statements, &labels[&label], 0)?;
+        }
+        self.output.flush().map_err(io_error)
+    }
+
+    fn locate_labels(
+        stmts: &[Stmt],
+        enter: &mut Vec<(usize, usize)>,
+        labels: &mut HashMap<String, Location>,
+    ) {
+        for (index, stmt) in stmts.iter().enumerate() {
+            if let Stmt::Label { name, .. } = stmt {
+                let location = Location {
+                    enter: enter.clone(),
+                    index,
+                };
+                labels.insert(name.clone(), location);
+            }
+            for (block, body) in stmt.blocks().into_iter().enumerate() {
+                enter.push((index, block));
+                Self::locate_labels(body, enter, labels);
+                enter.pop();
+            }
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt], from: usize) -> Result<Flow, Diagnostic> {
+        for stmt in stmts[from..].iter() {
+            if let Flow::Goto(label) = self.statement(stmt)? {
+                return Ok(Flow::Goto(label));
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // Continue running `stmts` as though control had jumped to `location`, entering the
+    // blocks on the way. A WHILE or FOR entered this way keeps looping afterwards, like C
+    // does; an IF branch entered this way skips the other branches.
+    fn resume(
+        &mut self,
+        stmts: &[Stmt],
+        location: &Location,
+        depth: usize,
+    ) -> Result<Flow, Diagnostic> {
+        let Some(&(index, block)) = location.enter.get(depth) else {
+            return self.block(stmts, location.index);
+        };
+        let stmt = &stmts[index];
+        if let Flow::Goto(label) = self.resume(stmt.blocks()[block], location, depth + 1)? {
+            return Ok(Flow::Goto(label));
+        }
+        match stmt {
+            Stmt::While { .. } => {
+                if let Flow::Goto(label) = self.statement(stmt)? {
+                    return Ok(Flow::Goto(label));
+                }
+            }
+            Stmt::For {
+                name, body, span, ..
+            } => {
+                self.step(name, span.start);
+                if let Flow::Goto(label) = self.count(name, body, span.start)? {
+                    return Ok(Flow::Goto(label));
+                }
+            }
+            _ => {}
+        }
+        self.block(stmts, index + 1)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<Flow, Diagnostic> {
+        match stmt {
+            Stmt::Print { value, .. } => match value {
+                PrintValue::String(text) => {
+                    write!(self.output, "{}", text).map_err(io_error)?;
+                }
+                PrintValue::Expr(expr) => {
+                    // printf("%.2f\n", (float)(expr))
+                    let value = self.expression(expr)?.as_f32() as f64;
+                    writeln!(self.output, "{}", format_float(value)).map_err(io_error)?;
+                }
+            },
+            Stmt::If {
+                condition,
+                body,
+                elseifs,
+                else_body,
+                ..
+            } => {
+                if self.condition(condition)? {
+                    return self.block(body, 0);
+                }
+                for (condition, body) in elseifs.iter() {
+                    if self.condition(condition)? {
+                        return self.block(body, 0);
+                    }
+                }
+                if let Some(body) = else_body {
+                    return self.block(body, 0);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                while self.condition(condition)? {
+                    if let Flow::Goto(label) = self.block(body, 0)? {
+                        return Ok(Flow::Goto(label));
+                    }
+                }
+            }
+            Stmt::For {
+                name,
+                start,
+                end,
+                step,
+                body,
+                span,
+            } => {
+                let start = self.expression(start)?.as_f32();
+                self.variables.insert(name.clone(), start);
+                let end = self.expression(end)?.as_f32();
+                let step = match step {
+                    Some(step) => self.expression(step)?.as_f32(),
+                    None => 1.0,
+                };
+                self.loops.insert(span.start, (end, step));
+                return self.count(name, body, span.start);
+            }
+            Stmt::Label { .. } => {}
+            Stmt::Goto { name, .. } => return Ok(Flow::Goto(name.clone())),
+            Stmt::Let { name, value, .. } => {
+                let value = self.expression(value)?.as_f32();
+                self.variables.insert(name.clone(), value);
+            }
+            Stmt::Input { name, .. } => {
+                // if(0 == scanf("%f", &x)) { x = 0; scanf("%*s"); }
+                self.output.flush().map_err(io_error)?;
+                match self.scan_float().map_err(io_error)? {
+                    Scan::Value(value) => {
+                        self.variables.insert(name.clone(), value);
+                    }
+                    Scan::NoMatch => {
+                        self.variables.insert(name.clone(), 0.0);
+                        self.skip_word().map_err(io_error)?;
+                    }
+                    // scanf returns EOF rather than 0, so the variable is left alone.
+                    Scan::Eof => {}
+                }
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // Run a FOR body until the variable passes the end, stepping it after each pass.
+    fn count(&mut self, name: &str, body: &[Stmt], key: usize) -> Result<Flow, Diagnostic> {
+        let (end, step) = self.loops.get(&key).copied().unwrap_or((0.0, 1.0));
+        loop {
+            let value = self.variables.get(name).copied().unwrap_or(0.0);
+            // step<0 ? i>=end : i<=end
+            let running = if step < 0.0 {
+                value >= end
+            } else {
+                value <= end
+            };
+            if !running {
+                return Ok(Flow::Normal);
+            }
+            if let Flow::Goto(label) = self.block(body, 0)? {
+                return Ok(Flow::Goto(label));
+            }
+            self.step(name, key);
+        }
+    }
+
+    fn step(&mut self, name: &str, key: usize) {
+        let (_, step) = self.loops.get(&key).copied().unwrap_or((0.0, 1.0));
+        let value = self.variables.get(name).copied().unwrap_or(0.0);
+        self.variables.insert(name.to_string(), value + step);
+    }
+
+    // AND and OR short-circuit like `&&` and `||`, so the right side may never run.
+    fn condition(&mut self, condition: &Condition) -> Result<bool, Diagnostic> {
+        Ok(match condition {
+            Condition::Compare {
+                op, left, right, ..
+            } => {
+                let left = self.expression(left)?;
+                let right = self.expression(right)?;
+                match (left, right) {
+                    (Value::Int(a), Value::Int(b)) => compare(*op, a, b),
+                    (Value::Double(_), _) | (_, Value::Double(_)) => {
+                        compare(*op, left.as_f64(), right.as_f64())
+                    }
+                    _ => compare(*op, left.as_f32(), right.as_f32()),
+                }
+            }
+            Condition::Not { operand, .. } => !self.condition(operand)?,
+            Condition::Logical {
+                op: LogicalOp::And,
+                left,
+                right,
+                ..
+            } => self.condition(left)? && self.condition(right)?,
+            Condition::Logical {
+                op: LogicalOp::Or,
+                left,
+                right,
+                ..
+            } => self.condition(left)? || self.condition(right)?,
+            Condition::Group { inner, .. } => self.condition(inner)?,
+        })
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<Value, Diagnostic> {
+        match expr {
+            Expr::Binary {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                let left = self.expression(left)?;
+                let right = self.expression(right)?;
+                binary(*op, left, right, *span)
+            }
+            Expr::Unary { op, operand, .. } => {
+                let value = self.expression(operand)?;
+                Ok(match (op, value) {
+                    (UnaryOp::Plus, value) => value,
+                    (UnaryOp::Minus, Value::Int(value)) => Value::Int(value.wrapping_neg()),
+                    (UnaryOp::Minus, Value::Float(value)) => Value::Float(-value),
+                    (UnaryOp::Minus, Value::Double(value)) => Value::Double(-value),
+                })
+            }
+            Expr::Group { inner, .. } => self.expression(inner),
+            Expr::Number { text, span } => number(text, *span),
+            // Variables are only readable after an assignment, and the parser checks that
+            // in source order; a GOTO can still skip the assignment, where C would read an
+            // indeterminate value, so use 0.
+            Expr::Var { name, .. } => Ok(Value::Float(
+                self.variables.get(name).copied().unwrap_or(0.0),
+            )),
+        }
+    }
+
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input.fill_buf()?.first().copied())
+    }
+
+    fn skip_whitespace(&mut self) -> io::Result<()> {
+        while let Some(byte) = self.peek_byte()? {
+            if !byte.is_ascii_whitespace() {
+                break;
+            }
+            self.input.consume(1);
+        }
+        Ok(())
+    }
+
+    // Read a float the way glibc's `scanf("%f")` does: skip whitespace, then take the
+    // longest prefix that looks like a decimal or hexadecimal number, `inf`, `infinity` or
+    // `nan`, leaving the rest of the input unread.
+    fn scan_float(&mut self) -> io::Result<Scan> {
+        self.skip_whitespace()?;
+        if self.peek_byte()?.is_none() {
+            return Ok(Scan::Eof);
+        }
+        let mut text = String::new();
+        if let Some(sign @ (b'+' | b'-')) = self.peek_byte()? {
+            text.push(sign as char);
+            self.input.consume(1);
+        }
+        // Negate rather than multiply, which would keep the sign of a NaN.
+        let negative = text == "-";
+        let sign = |value: f32| if negative { -value } else { value };
+        match self.peek_byte()? {
+            Some(b'i' | b'I') => {
+                if !self.take_word("inf")? {
+                    return Ok(Scan::NoMatch);
+                }
+                if matches!(self.peek_byte()?, Some(b'i' | b'I')) && !self.take_word("inity")? {
+                    return Ok(Scan::NoMatch);
+                }
+                return Ok(Scan::Value(sign(f32::INFINITY)));
+            }
+            Some(b'n' | b'N') => {
+                if !self.take_word("nan")? {
+                    return Ok(Scan::NoMatch);
+                }
+                return Ok(Scan::Value(sign(f32::NAN)));
+            }
+            _ => {}
+        }
+        let mut digits = 0;
+        if self.peek_byte()? == Some(b'0') {
+            self.input.consume(1);
+            if let Some(b'x' | b'X') = self.peek_byte()? {
+                self.input.consume(1);
+                return Ok(match self.scan_hex()? {
+                    Some(value) => Scan::Value(sign(value)),
+                    None => Scan::NoMatch,
+                });
+            }
+            text.push('0');
+            digits += 1;
+        }
+        digits += self.take_digits(&mut text)?;
+        if self.peek_byte()? == Some(b'.') {
+            text.push('.');
+            self.input.consume(1);
+            digits += self.take_digits(&mut text)?;
+        }
+        if digits == 0 {
+            return Ok(Scan::NoMatch);
+        }
+        if let Some(e @ (b'e' | b'E')) = self.peek_byte()? {
+            let mut exponent = (e as char).to_string();
+            self.input.consume(1);
+            if let Some(sign @ (b'+' | b'-')) = self.peek_byte()? {
+                exponent.push(sign as char);
+                self.input.consume(1);
+            }
+            if self.take_digits(&mut exponent)? > 0 {
+                text.push_str(&exponent);
+            }
+        }
+        Ok(Scan::Value(text.parse().unwrap_or(0.0)))
+    }
+
+    // The rest of a hexadecimal float after its `0x`: hex digits with an optional point,
+    // then an optional binary exponent, as in `0x1.8p3`. As in glibc, a lone point still
+    // reads as 0, but no digits at all do not match.
+    fn scan_hex(&mut self) -> io::Result<Option<f32>> {
+        let mut mantissa = 0.0;
+        let digits = self.take_hex_digits(&mut mantissa)?;
+        let point = self.peek_byte()? == Some(b'.');
+        let mut fraction = 0;
+        if point {
+            self.input.consume(1);
+            fraction = self.take_hex_digits(&mut mantissa)?;
+        }
+        if digits == 0 && !point {
+            return Ok(None);
+        }
+        let mut exponent = 0;
+        if let Some(b'p' | b'P') = self.peek_byte()? {
+            self.input.consume(1);
+            let mut negative = false;
+            if let Some(sign @ (b'+' | b'-')) = self.peek_byte()? {
+                negative = sign == b'-';
+                self.input.consume(1);
+            }
+            let mut text = String::new();
+            self.take_digits(&mut text)?;
+            // Saturate, as anything this far out is 0 or infinity anyway.
+            exponent = text.bytes().fold(0i32, |exponent, digit| {
+                exponent
+                    .saturating_mul(10)
+                    .saturating_add((digit - b'0') as i32)
+            });
+            if negative {
+                exponent = -exponent;
+            }
+        }
+        let scale = exponent.saturating_sub(4 * fraction as i32);
+        Ok(Some((mantissa * 2f64.powi(scale)) as f32))
+    }
+
+    fn take_hex_digits(&mut self, mantissa: &mut f64) -> io::Result<usize> {
+        let mut count = 0;
+        while let Some(byte) = self.peek_byte()? {
+            let Some(digit) = (byte as char).to_digit(16) else {
+                break;
+            };
+            *mantissa = *mantissa * 16.0 + digit as f64;
+            self.input.consume(1);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // Read `word`, ignoring case, for `inf` and `nan`. Like glibc, give up after reading
+    // the first character that does not fit.
+    fn take_word(&mut self, word: &str) -> io::Result<bool> {
+        for expected in word.bytes() {
+            match self.peek_byte()? {
+                Some(byte) => {
+                    self.input.consume(1);
+                    if !byte.eq_ignore_ascii_case(&expected) {
+                        return Ok(false);
+                    }
+                }
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    fn take_digits(&mut self, text: &mut String) -> io::Result<usize> {
+        let mut count = 0;
+        while let Some(byte) = self.peek_byte()? {
+            if !byte.is_ascii_digit() {
+                break;
+            }
+            text.push(byte as char);
+            self.input.consume(1);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // `scanf("%*s")`: skip one whitespace-delimited word.
+    fn skip_word(&mut self) -> io::Result<()> {
+        self.skip_whitespace()?;
+        while let Some(byte) = self.peek_byte()? {
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            self.input.consume(1);
+        }
+        Ok(())
+    }
+}
+
+fn compare<T: PartialOrd>(op: CompareOp, a: T, b: T) -> bool {
+    match op {
+        CompareOp::Eq => a == b,
+        CompareOp::NotEq => a != b,
+        CompareOp::Lt => a < b,
+        CompareOp::LtEq => a <= b,
+        CompareOp::Gt => a > b,
+        CompareOp::GtEq => a >= b,
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value, span: Span) -> Result<Value, Diagnostic> {
+    Ok(match (left, right) {
+        // These are the C library's double functions, whatever the operand types.
+        _ if op.is_call() => {
+            let (a, b) = (left.as_f64(), right.as_f64());
+            Value::Double(match op {
+                BinaryOp::Mod => a % b,
+                BinaryOp::IntDiv => (a / b).trunc(),
+                _ => a.powf(b),
+            })
+        }
+        (Value::Int(a), Value::Int(b)) => Value::Int(match op {
+            BinaryOp::Add => a.wrapping_add(b),
+            BinaryOp::Sub => a.wrapping_sub(b),
+            BinaryOp::Mul => a.wrapping_mul(b),
+            BinaryOp::Div => {
+                if b == 0 {
+                    return Err(Diagnostic::error(
+                        "E0302",
+                        "Integer division by zero".to_string(),
+                        Some(span),
+                    ));
+                }
+                a.wrapping_div(b)
+            }
+            _ => unreachable!(),
+        }),
+        (Value::Double(_), _) | (_, Value::Double(_)) => {
+            let (a, b) = (left.as_f64(), right.as_f64());
+            Value::Double(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                _ => unreachable!(),
+            })
+        }
+        _ => {
+            let (a, b) = (left.as_f32(), right.as_f32());
+            Value::Float(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                _ => unreachable!(),
+            })
+        }
+    })
+}
+
+// A literal is an `int` in C unless it has a decimal point or an exponent, which make it
+// a `double`.
+fn number(text: &str, span: Span) -> Result<Value, Diagnostic> {
+    let value = if text.contains(['.', 'e']) {
+        text.parse().map(Value::Double).ok()
+    } else {
+        text.parse().map(Value::Int).ok()
+    };
+    value.ok_or_else(|| {
+        Diagnostic::error(
+            "E0303",
+            format!("Invalid number literal: {:?}", text),
+            Some(span),
+        )
+    })
+}
+
+// `printf("%.2f")`, including glibc's spelling of infinities and NaNs.
+fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        if value.is_sign_negative() {
+            "-nan".to_string()
+        } else {
+            "nan".to_string()
+        }
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn io_error(err: io::Error) -> Diagnostic {
+    Diagnostic::error("E0301", format!("I/O error: {}", err), None)
+}
@@ -1,221 +1,646 @@
+use crate::Diagnostic;
+use crate::Keywords;
+use crate::Span;
 use crate::Token;
 use crate::TokenType;
-use std::process::exit;
+use crate::Trivia;
+use crate::TriviaKind;
+use unicode_ident::{is_xid_continue, is_xid_start};
 
 #[derive(Debug, Clone)]
 pub struct Lexer {
     pub source: String,
     pub cur_char: char,
-    pub cur_pos: i32,
+    // Byte offset of the character after cur_char, where next_char reads from.
+    pub cur_pos: usize,
+    // Byte offset, line and column of cur_char.
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    // Set once the EOF token has been produced.
+    pub finished: bool,
+    // Set when an error stops on a character that is not part of the bad token, such as
+    // the newline after an unterminated string, so the next token starts there.
+    pub held: bool,
+    // Keep the whitespace and comments before each token as its `leading` trivia, so the
+    // tokens reproduce the source exactly.
+    pub trivia: bool,
+    pub keywords: Keywords,
+    // Problems with tokens that were still produced, such as a number literal that is
+    // too large, for the consumer to collect.
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Lexer {
-    //Process the next character.
-    pub fn next_char(&mut self) {
-        if self.cur_pos < self.source.len() as i32 {
-            self.cur_char = self.source.chars().nth(self.cur_pos as usize).unwrap();
-            self.cur_pos += 1;
-        } else {
-            self.cur_char = '\0';
+    pub fn new(source: String) -> Lexer {
+        Lexer {
+            source,
+            cur_char: ' ',
+            cur_pos: 0,
+            offset: 0,
+            line: 1,
+            column: 1,
+            finished: false,
+            held: false,
+            trivia: false,
+            keywords: Keywords::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // Process the next character, decoding it at the byte cursor so lexing is linear in the
+    // source length.
+    fn next_char(&mut self) {
+        // Step the line and column past the character we are leaving, if there is one.
+        if self.cur_pos > self.offset {
+            if self.cur_char == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset = self.cur_pos;
+        match self.source[self.cur_pos..].chars().next() {
+            Some(c) => {
+                self.cur_char = c;
+                self.cur_pos += c.len_utf8();
+            }
+            None => self.cur_char = '\0',
         }
     }
 
-    // Return the lookahead character.
+    // Whether the whole source has been read. `cur_char` is then '\0', but so it is for a
+    // NUL in the source, which is lexed as an unknown character.
+    fn at_end(&self) -> bool {
+        self.offset >= self.source.len()
+    }
+
+    // Return the lookahead character, or '\0' past the end of the source.
     fn peek(&self) -> char {
-        if self.cur_pos + 1 < self.source.len() as i32 {
-            self.source.chars().nth(self.cur_pos as usize).unwrap()
+        self.source[self.cur_pos..].chars().next().unwrap_or('\0')
+    }
+
+    // Span running from the start of `start` to the end of the current character.
+    fn span_from(&self, start: Span) -> Span {
+        let end = if self.at_end() {
+            self.offset
         } else {
-            '\0'
-        }
+            self.offset + self.cur_char.len_utf8()
+        };
+        Span { end, ..start }
+    }
+
+    // Span covering just the current character.
+    fn cur_span(&self) -> Span {
+        self.span_from(Span {
+            start: self.offset,
+            end: self.offset,
+            line: self.line,
+            column: self.column,
+        })
     }
 
-    // Invalid token found, print error message and exit.
-    fn abort(&self, message: &str) {
-        println!("{}", message);
-        exit(0);
+    // Invalid token found, return an error pointing at the current character.
+    fn abort<T>(&self, code: &'static str, message: String) -> Result<T, Diagnostic> {
+        Err(Diagnostic::error(code, message, Some(self.cur_span())))
     }
 
     // Skip whitespace except newlines, which we will use to indicate the end of a statement.
-    fn skip_whitespace(&mut self) {
+    fn skip_whitespace(&mut self) -> Option<TriviaKind> {
+        if self.cur_char != ' ' && self.cur_char != '\t' && self.cur_char != '\r' {
+            return None;
+        }
         while self.cur_char == ' ' || self.cur_char == '\t' || self.cur_char == '\r' {
             self.next_char();
         }
+        Some(TriviaKind::Whitespace)
     }
 
-    // Skip comments in the code.
-    fn skip_comment(&mut self) {
-        if self.cur_char == '#' {
-            loop {
-                if self.cur_char == '\n' {
-                    if self.peek() == '#' {
-                        self.next_char();
-                    } else {
-                        self.next_char();
-                        break;
-                    }
-                }
+    // Skip a comment starting at the current character, returning which kind it was.
+    // A `#` comment runs to the end of the line and leaves the newline to be lexed; a
+    // `#[ ... ]#` block comment may span lines and nest.
+    fn skip_comment(&mut self) -> Result<Option<TriviaKind>, Diagnostic> {
+        if self.cur_char != '#' {
+            return Ok(None);
+        }
+        if self.peek() != '[' {
+            while self.cur_char != '\n' && !self.at_end() {
                 self.next_char();
             }
+            return Ok(Some(TriviaKind::LineComment));
+        }
+
+        let start = self.cur_span();
+        let mut depth = 0;
+        loop {
+            match (self.cur_char, self.peek()) {
+                _ if self.at_end() => {
+                    let opening = Span {
+                        end: start.start + 2,
+                        ..start
+                    };
+                    return Err(Diagnostic::error(
+                        "E0006",
+                        "Unterminated block comment.".to_string(),
+                        Some(opening),
+                    ));
+                }
+                ('#', '[') => {
+                    depth += 1;
+                    self.next_char();
+                }
+                (']', '#') => {
+                    depth -= 1;
+                    self.next_char();
+                }
+                _ => {}
+            }
+            self.next_char();
+            if depth == 0 {
+                return Ok(Some(TriviaKind::BlockComment));
+            }
         }
     }
 
-    // Return the next token.
-    pub fn get_token(&mut self) -> Option<Token> {
-        self.skip_whitespace();
-        self.skip_comment();
+    // Skip the whitespace and comments before a token, returning them in trivia mode.
+    fn skip_trivia(&mut self) -> Result<Vec<Trivia>, Diagnostic> {
+        let mut leading = Vec::new();
+        loop {
+            let start = self.cur_span();
+            let kind = match self.skip_whitespace() {
+                Some(kind) => kind,
+                None => match self.skip_comment()? {
+                    Some(kind) => kind,
+                    None => return Ok(leading),
+                },
+            };
+            if self.trivia {
+                leading.push(Trivia {
+                    kind,
+                    text: self.source[start.start..self.offset].to_string(),
+                    span: Span {
+                        end: self.offset,
+                        ..start
+                    },
+                });
+            }
+        }
+    }
+
+    // Return the token starting at the current character.
+    fn get_token(&mut self) -> Result<Token, Diagnostic> {
+        let leading = self.skip_trivia()?;
+        let mut token = self.lex_token()?;
+        token.leading = leading;
+        Ok(token)
+    }
+
+    // Lex the token at the current character, past any trivia.
+    fn lex_token(&mut self) -> Result<Token, Diagnostic> {
+        let start = self.cur_span();
         match self.cur_char {
-            '+' => Some(Token {
+            '+' => Ok(Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::PLUS,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
             }),
-            '-' => Some(Token {
+            '-' => Ok(Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::MINUS,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
             }),
-            '*' => Some(Token {
+            '*' => Ok(Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::ASTERISK,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
             }),
-            '/' => Some(Token {
+            '/' => Ok(Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::SLASH,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
+            }),
+            '%' => Ok(Token {
+                text: self.cur_char.to_string(),
+                kind: TokenType::PERCENT,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
+            }),
+            '\\' => Ok(Token {
+                text: self.cur_char.to_string(),
+                kind: TokenType::BACKSLASH,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
+            }),
+            '^' => Ok(Token {
+                text: self.cur_char.to_string(),
+                kind: TokenType::CARET,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
+            }),
+            '(' => Ok(Token {
+                text: self.cur_char.to_string(),
+                kind: TokenType::LPAREN,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
+            }),
+            ')' => Ok(Token {
+                text: self.cur_char.to_string(),
+                kind: TokenType::RPAREN,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
             }),
-            '\n' => Some(Token {
+            '\n' => Ok(Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::NEWLINE,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
             }),
-            '\0' => Some(Token {
+            '\0' if self.at_end() => Ok(Token {
                 text: '\0'.to_string(),
                 kind: TokenType::EOF,
+                span: self.span_from(start),
+                value: None,
+                leading: Vec::new(),
             }),
             '>' => {
                 if self.peek() == '=' {
-                    let last_char = self.cur_char.clone();
+                    let last_char = self.cur_char;
                     self.next_char();
-                    Some(Token {
+                    Ok(Token {
                         text: last_char.to_string() + &self.cur_char.to_string(),
                         kind: TokenType::GTEQ,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 } else {
-                    Some(Token {
+                    Ok(Token {
                         text: '>'.to_string(),
                         kind: TokenType::GT,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 }
             }
             '<' => {
                 if self.peek() == '=' {
-                    let last_char = self.cur_char.clone();
+                    let last_char = self.cur_char;
                     self.next_char();
-                    Some(Token {
+                    Ok(Token {
                         text: last_char.to_string() + &self.cur_char.to_string(),
                         kind: TokenType::LTEQ,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 } else {
-                    Some(Token {
+                    Ok(Token {
                         text: '<'.to_string(),
                         kind: TokenType::LT,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 }
             }
             '=' => {
                 if self.peek() == '=' {
-                    let last_char = self.cur_char.clone();
+                    let last_char = self.cur_char;
                     self.next_char();
-                    Some(Token {
+                    Ok(Token {
                         text: last_char.to_string() + &self.cur_char.to_string(),
                         kind: TokenType::EQEQ,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 } else {
-                    Some(Token {
+                    Ok(Token {
                         text: '='.to_string(),
                         kind: TokenType::EQ,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 }
             }
             '!' => {
                 if self.peek() == '=' {
-                    let last_char = self.cur_char.clone();
+                    let last_char = self.cur_char;
                     self.next_char();
-                    Some(Token {
+                    Ok(Token {
                         text: last_char.to_string() + &self.cur_char.to_string(),
                         kind: TokenType::NOTEQ,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
                     })
                 } else {
-                    None
+                    self.abort("E0001", format!("Expected !=, got !{}", self.peek()))
                 }
             }
             '"' => {
                 self.next_char();
-                let start_pos = self.cur_pos.clone();
+                let start_pos = self.offset;
 
+                // Keep scanning to the closing quote after a bad escape, so lexing resumes
+                // after the string rather than inside it.
+                let mut value = String::new();
+                let mut illegal = None;
                 while self.cur_char != '"' {
-                    if self.cur_char == '\r'
-                        || self.cur_char == '\n'
-                        || self.cur_char == '\\'
-                        || self.cur_char == '%'
-                    {
-                        self.abort(
-                            &format!(
-                                "Illegal character in string: {:?} at position {:?}",
-                                self.cur_char, self.cur_pos
-                            )
-                            .to_string(),
-                        );
+                    if self.cur_char == '\r' || self.cur_char == '\n' || self.at_end() {
+                        // Point at the string, and leave the end of the line to be lexed.
+                        self.held = true;
+                        return Err(Diagnostic::error(
+                            "E0005",
+                            "Unterminated string.".to_string(),
+                            Some(Span {
+                                end: self.offset,
+                                ..start
+                            }),
+                        ));
+                    }
+                    if self.cur_char == '\\' {
+                        let escape_start = self.cur_span();
+                        self.next_char();
+                        match self.cur_char {
+                            'n' => value.push('\n'),
+                            't' => value.push('\t'),
+                            '"' => value.push('"'),
+                            '\\' => value.push('\\'),
+                            // Leave the end of the line to the unterminated string check.
+                            '\r' | '\n' => continue,
+                            _ if self.at_end() => continue,
+                            c => {
+                                if illegal.is_none() {
+                                    illegal = Some(Diagnostic::error(
+                                        "E0002",
+                                        format!("Unknown escape sequence in string: \\{}", c),
+                                        Some(self.span_from(escape_start)),
+                                    ));
+                                }
+                            }
+                        }
+                    } else {
+                        value.push(self.cur_char);
                     }
                     self.next_char();
                 }
-                Some(Token {
-                    text: self.source[(start_pos - 1) as usize..(self.cur_pos - 1) as usize]
-                        .to_string(),
+                if let Some(diagnostic) = illegal {
+                    return Err(diagnostic);
+                }
+                Ok(Token {
+                    text: self.source[start_pos..self.offset].to_string(),
                     kind: TokenType::STRING,
+                    span: self.span_from(start),
+                    value: Some(value),
+                    leading: Vec::new(),
                 })
             }
-            '0'..='9' => {
-                let start_pos = self.cur_pos;
-
-                while self.cur_char.is_ascii_digit() {
+            '0'..='9' => self.number(start),
+            // Identifiers follow Unicode's XID_Start and XID_Continue, as Rust's do, with
+            // `_` also allowed at the start. So combining marks may follow a letter, but
+            // superscripts and other numeric symbols are not part of a name.
+            c if is_xid_start(c) || c == '_' => {
+                while is_xid_continue(self.peek()) {
                     self.next_char();
                 }
-                if self.cur_char == '.' {
-                    self.next_char();
-                    if !self.peek().is_ascii_digit() {
-                        self.abort("Illegal character in number.");
-                    }
-                    while self.peek().is_ascii_digit() {
-                        self.next_char();
-                    }
+
+                let token_text = &self.source[start.start..self.cur_pos];
+
+                match self.keywords.lookup(token_text) {
+                    Some(keyword) => Ok(Token {
+                        text: token_text.to_string(),
+                        kind: keyword,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
+                    }),
+                    None => Ok(Token {
+                        text: token_text.to_string(),
+                        kind: TokenType::IDENT,
+                        span: self.span_from(start),
+                        value: None,
+                        leading: Vec::new(),
+                    }),
                 }
-                Some(Token {
-                    text: self.source[(start_pos - 1) as usize..(self.cur_pos - 1) as usize]
-                        .to_string(),
-                    kind: TokenType::NUMBER,
-                })
             }
-            'A'..='Z' | 'a'..='z' => {
-                let start_pos = self.cur_pos;
+            _ => self.abort("E0004", format!("Unknown token: {:?}", self.cur_char)),
+        }
+    }
 
-                while self.peek().is_ascii_alphanumeric() {
+    // Lex a number literal: decimal with an optional fraction and exponent (`1.5e-3`),
+    // hexadecimal (`0x1F`) or binary (`0b1010`), with `_` allowed between digits. The
+    // token's value is the literal spelled for C: integers that fit an `int` in decimal,
+    // anything else as a double with `.` or `e`.
+    fn number(&mut self, start: Span) -> Result<Token, Diagnostic> {
+        let radix = match (self.cur_char, self.peek()) {
+            ('0', 'x' | 'X') => 16,
+            ('0', 'b' | 'B') => 2,
+            _ => 10,
+        };
+        let value = if radix != 10 {
+            self.next_char();
+            let digits = self.digits(radix)?;
+            if digits.is_empty() {
+                let name = if radix == 16 { "hexadecimal" } else { "binary" };
+                return self.bad_number(format!("Expected {} digits.", name));
+            }
+            if self.peek().is_ascii_alphanumeric() {
+                self.next_char();
+                return self.bad_number(format!(
+                    "Illegal digit in base {} number: {:?}",
+                    radix, self.cur_char
+                ));
+            }
+            self.integer(&digits, radix, start)
+        } else {
+            let mut digits = self.cur_char.to_string();
+            digits.push_str(&self.digits(10)?);
+            let mut double = false;
+            if self.peek() == '.' {
+                self.next_char();
+                // Must have at least one digit after the decimal point.
+                if !self.peek().is_ascii_digit() {
+                    return self.bad_number("Illegal character in number.".to_string());
+                }
+                digits.push('.');
+                digits.push_str(&self.digits(10)?);
+                double = true;
+            }
+            if self.peek() == 'e' || self.peek() == 'E' {
+                self.next_char();
+                digits.push('e');
+                if self.peek() == '+' || self.peek() == '-' {
                     self.next_char();
+                    digits.push(self.cur_char);
                 }
+                if !self.peek().is_ascii_digit() {
+                    return self.bad_number("Expected digits in the exponent.".to_string());
+                }
+                digits.push_str(&self.digits(10)?);
+                double = true;
+            }
+            if double {
+                self.double(digits, start)
+            } else {
+                self.integer(&digits, 10, start)
+            }
+        };
+        Ok(Token {
+            text: self.source[start.start..self.cur_pos].to_string(),
+            kind: TokenType::NUMBER,
+            span: self.span_from(start),
+            value: Some(value),
+            leading: Vec::new(),
+        })
+    }
 
-                let token_text = &self.source[(start_pos - 1) as usize..(self.cur_pos) as usize];
+    // Consume the digits in `radix` that follow the current character, and return them
+    // without their `_` separators, which may only appear between two digits.
+    fn digits(&mut self, radix: u32) -> Result<String, Diagnostic> {
+        let mut digits = String::new();
+        loop {
+            let c = self.peek();
+            if c == '_' {
+                let after_digit = self.cur_char.is_digit(radix);
+                self.next_char();
+                if !after_digit || !self.peek().is_digit(radix) {
+                    return self.bad_number(
+                        "A `_` digit separator must be between two digits.".to_string(),
+                    );
+                }
+            } else if c.is_digit(radix) {
+                self.next_char();
+                digits.push(c);
+            } else {
+                return Ok(digits);
+            }
+        }
+    }
 
-                let keyword = Token::check_if_keyword(&token_text);
+    // Report a malformed number at the current character and skip the rest of it.
+    fn bad_number<T>(&mut self, message: String) -> Result<T, Diagnostic> {
+        let span = self.cur_span();
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' || self.peek() == '.' {
+            self.next_char();
+        }
+        Err(Diagnostic::error("E0003", message, Some(span)))
+    }
 
-                if keyword.is_none() {
-                    Some(Token {
-                        text: token_text.to_string(),
-                        kind: TokenType::IDENT,
-                    })
+    // The C spelling of an integer literal, given its digits in `radix` without
+    // separators. One too large for an `int` would be a `long` in C, but every value ends
+    // up in a float variable, so it is spelled as a double instead.
+    fn integer(&mut self, digits: &str, radix: u32, start: Span) -> String {
+        let (spelled, exact) = match i32::from_str_radix(digits, radix) {
+            Ok(value) => (value.to_string(), value as f32 as f64 == value as f64),
+            Err(_) => {
+                let value = digits
+                    .chars()
+                    .filter_map(|c| c.to_digit(radix))
+                    .fold(0.0, |value: f64, digit| value * radix as f64 + digit as f64);
+                let warnings = self.diagnostics.len();
+                let spelled = if radix == 10 {
+                    self.double(format!("{}.0", digits), start)
                 } else {
-                    Some(Token {
-                        text: token_text.to_string(),
-                        kind: keyword.unwrap(),
-                    })
-                }
+                    self.double(format!("{:e}", value), start)
+                };
+                // Only add the float warning when the double had nothing worse to report.
+                let exact = self.diagnostics.len() > warnings || value as f32 as f64 == value;
+                (spelled, exact)
             }
-            _ => None,
+        };
+        if !exact {
+            let text = &self.source[start.start..self.cur_pos];
+            self.diagnostics.push(Diagnostic::warning(
+                "W0001",
+                format!(
+                    "Integer literal {} cannot be stored exactly in a float variable, it becomes {}.",
+                    text,
+                    spelled.parse::<f64>().unwrap_or_default() as f32 as f64
+                ),
+                Some(self.span_from(start)),
+            ));
+        }
+        spelled
+    }
+
+    // The C spelling of a double literal, given its digits without separators.
+    fn double(&mut self, digits: String, start: Span) -> String {
+        let text = &self.source[start.start..self.cur_pos];
+        let span = self.span_from(start);
+        let value: f64 = digits.parse().unwrap_or(f64::INFINITY);
+        let mantissa = digits.split('e').next().unwrap_or("");
+        let significant = mantissa
+            .chars()
+            .filter(char::is_ascii_digit)
+            .skip_while(|&c| c == '0')
+            .count();
+        if value.is_infinite() {
+            self.diagnostics.push(Diagnostic::error(
+                "E0007",
+                format!("Number literal {} is too large for a double.", text),
+                Some(span),
+            ));
+            return digits;
+        }
+        let warning = if value == 0.0 && significant > 0 {
+            Some("is too small for a double and becomes 0")
+        } else if value as f32 == f32::INFINITY {
+            Some("is too large for a float variable")
+        } else if significant > 17 {
+            Some("has more digits than a double can hold")
+        } else {
+            None
+        };
+        if let Some(warning) = warning {
+            self.diagnostics.push(Diagnostic::warning(
+                "W0001",
+                format!("Number literal {} {}.", text, warning),
+                Some(span),
+            ));
+        }
+        digits
+    }
+}
+
+// Yields complete tokens, ending with a single EOF. A bad character is yielded as an error
+// and lexing carries on after it.
+impl Iterator for Lexer {
+    type Item = Result<Token, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        if !self.held {
+            self.next_char();
+        }
+        self.held = false;
+        let token = self.get_token();
+        if let Ok(Token {
+            kind: TokenType::EOF,
+            ..
+        }) = token
+        {
+            self.finished = true;
         }
+        Some(token)
     }
 }
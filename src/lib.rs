@@ -0,0 +1,112 @@
+//https://austinhenley.com/blog/teenytinycompiler1.html
+
+pub mod ast;
+pub mod cli;
+pub mod codegen;
+pub mod diagnostic;
+pub mod emitter;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+pub mod render;
+pub mod repl;
+pub mod token;
+pub mod toolchain;
+pub use ast::Program;
+pub use codegen::*;
+pub use diagnostic::*;
+pub use emitter::*;
+pub use interpreter::*;
+pub use lexer::*;
+pub use parser::*;
+pub use render::*;
+pub use repl::*;
+pub use token::*;
+pub use toolchain::*;
+
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    // Emit `#line` directives mapping the C back to the Teeny Tiny source.
+    pub line_directives: bool,
+    // The source file name for the `#line` directives.
+    pub source_name: String,
+    // The keywords to recognise, including whether their case matters.
+    pub keywords: Keywords,
+}
+
+impl Default for CompileOptions {
+    fn default() -> CompileOptions {
+        CompileOptions {
+            line_directives: true,
+            source_name: "<source>".to_string(),
+            keywords: Keywords::new(),
+        }
+    }
+}
+
+// The result of a successful compile.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub program: Program,
+    pub c_code: String,
+    // The C code calls into the maths library, so it must be linked with `-lm`.
+    pub needs_libm: bool,
+    // Warnings found on the way, for the caller to report.
+    pub warnings: Vec<Diagnostic>,
+}
+
+// Compile Teeny Tiny source to C. Never touches the filesystem or stdout; every problem
+// comes back as a diagnostic for the caller to render.
+pub fn compile(source: &str, options: &CompileOptions) -> Result<Output, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(source.to_string());
+    lexer.keywords = options.keywords.clone();
+    let mut parser = Parser::new(lexer);
+    let program = parser.program()?;
+
+    let mut emitter = Emitter::new(String::new());
+    let mut codegen = CodeGen::new(&mut emitter);
+    codegen.line_directives = options.line_directives;
+    codegen.source_name = options.source_name.clone();
+    codegen.program(&program);
+
+    let needs_libm = codegen.needs_libm;
+
+    Ok(Output {
+        program,
+        c_code: emitter.output(),
+        needs_libm,
+        warnings: parser.diagnostics,
+    })
+}
+
+// Lex the whole source, ending with the EOF token. Bad characters are reported and skipped.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    lex(Lexer::new(source.to_string()))
+}
+
+// Lex the whole source keeping whitespace and comments, so that concatenating
+// `Token::source` over the tokens gives back the source byte for byte.
+pub fn tokenize_with_trivia(source: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(source.to_string());
+    lexer.trivia = true;
+    lex(lexer)
+}
+
+// Run a configured lexer to the end. Warnings are dropped unless there are errors to
+// report along with them.
+pub fn lex(mut lexer: Lexer) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    let mut tokens = Vec::new();
+    let mut diagnostics = Vec::new();
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+        diagnostics.append(&mut lexer.diagnostics);
+    }
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        Err(diagnostics)
+    } else {
+        Ok(tokens)
+    }
+}
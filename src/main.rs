@@ -1,47 +1,233 @@
-//https://austinhenley.com/blog/teenytinycompiler1.html
-
+use rust_basic_compiler::cli::*;
+use rust_basic_compiler::*;
 use std::env;
-use std::fs::File;
-use std::io::{self, Read};
-mod emitter;
-mod lexer;
-mod parser;
-mod token;
-use emitter::*;
-use lexer::*;
-use parser::*;
-use token::*;
-
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let file_path = &args[1];
-    println!("{:?}", file_path);
-    let mut file = File::open(file_path)?;
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
-    println!(
-        "input test.txt\n------------\n{}------------",
-        content.to_string()
-    );
-
-    let lexer = Lexer {
-        source: content.to_string(),
-        cur_char: ' ',
-        cur_pos: 0,
-    };
-    let mut emitter = Emitter::new("out.c".to_string());
-    let mut parser = Parser {
-        lexer,
-        emitter: &mut emitter,
-        cur_token: None,
-        peek_token: None,
-        symbols: Vec::new(),
-        labels_declared: Vec::new(),
-        labels_gotoed: Vec::new(),
-    };
-    parser.program();
-    emitter.write_file()?;
-    println!("Compiling completed.");
-
-    Ok(())
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{self, exit};
+
+fn main() {
+    let status = match parse_args(env::args().skip(1)) {
+        Ok(Command::Compile(args)) => compile_command(&args),
+        Ok(Command::Build(args)) => build_command(&args, false),
+        Ok(Command::Run(args)) => build_command(&args, true),
+        Ok(Command::Interpret(args)) => interpret_command(&args),
+        Ok(Command::Repl {
+            ignore_keyword_case,
+        }) => repl_command(ignore_keyword_case),
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            EXIT_SUCCESS
+        }
+        Ok(Command::Version) => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            EXIT_SUCCESS
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\nFor more information, try '--help'.", message);
+            EXIT_USAGE
+        }
+    };
+    exit(status);
+}
+
+fn compile_command(args: &Args) -> i32 {
+    let (file_name, content) = match read_source(&args.input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", args.input, err);
+            return EXIT_IO_ERROR;
+        }
+    };
+
+    let result = match args.emit {
+        Emit::Tokens => lex(lexer(args, &content)).map(|tokens| {
+            tokens
+                .iter()
+                .map(|token| {
+                    format!(
+                        "{}:{} {:?} {:?}\n",
+                        token.span.line, token.span.column, token.kind, token.text
+                    )
+                })
+                .collect::<String>()
+        }),
+        Emit::Ast => compile_source(args, &file_name, &content)
+            .map(|output| format!("{:#?}\n", output.program)),
+        Emit::C => compile_source(args, &file_name, &content).map(|output| output.c_code),
+    };
+
+    match result {
+        Ok(text) => {
+            let output = args.compile_output();
+            if let Err(err) = write_output(output, &text) {
+                eprintln!("error: could not write {}: {}", output, err);
+                return EXIT_IO_ERROR;
+            }
+            if !args.quiet && output != "-" {
+                eprintln!("Compiled {} to {}.", file_name, output);
+            }
+            EXIT_SUCCESS
+        }
+        Err(diagnostics) => failed(args, &diagnostics, &file_name, &content),
+    }
+}
+
+// Compile all the way to a native executable with the system C compiler, then optionally
+// run it from a temporary directory and exit with its status.
+fn build_command(args: &Args, run: bool) -> i32 {
+    let (file_name, content) = match read_source(&args.input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", args.input, err);
+            return EXIT_IO_ERROR;
+        }
+    };
+    let output = match compile_source(args, &file_name, &content) {
+        Ok(output) => output,
+        Err(diagnostics) => return failed(args, &diagnostics, &file_name, &content),
+    };
+
+    let mut toolchain = Toolchain::from_env();
+    if let Some(cc) = &args.cc {
+        toolchain.cc = cc.clone();
+    }
+    toolchain.flags.extend(args.cflags.iter().cloned());
+
+    if !run {
+        let exe = args.build_output();
+        if let Err(diagnostics) = toolchain.build(&output, &file_name, &content, &exe) {
+            return failed(args, &diagnostics, &file_name, &content);
+        }
+        if !args.quiet {
+            eprintln!("Built {} to {}.", file_name, exe.display());
+        }
+        return EXIT_SUCCESS;
+    }
+
+    let dir = match scratch_dir() {
+        Ok(dir) => dir,
+        Err(err) => {
+            eprintln!("error: could not create a temporary directory: {}", err);
+            return EXIT_IO_ERROR;
+        }
+    };
+    let exe = dir.join("program");
+    let status = match toolchain.build(&output, &file_name, &content, &exe) {
+        Ok(()) => match process::Command::new(&exe).status() {
+            Ok(status) => program_exit_status(status),
+            Err(err) => {
+                eprintln!("error: could not run {}: {}", exe.display(), err);
+                EXIT_IO_ERROR
+            }
+        },
+        Err(diagnostics) => failed(args, &diagnostics, &file_name, &content),
+    };
+    let _ = fs::remove_dir_all(&dir);
+    status
+}
+
+// Run the program with the built-in interpreter on this process's stdin and stdout.
+fn interpret_command(args: &Args) -> i32 {
+    let (file_name, content) = match read_source(&args.input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", args.input, err);
+            return EXIT_IO_ERROR;
+        }
+    };
+    let output = match compile_source(args, &file_name, &content) {
+        Ok(output) => output,
+        Err(diagnostics) => return failed(args, &diagnostics, &file_name, &content),
+    };
+    let mut interpreter = Interpreter::new(io::stdin().lock(), io::stdout().lock());
+    match interpreter.run(&output.program) {
+        Ok(()) => EXIT_SUCCESS,
+        Err(diagnostic) => {
+            report(&[diagnostic], &file_name, &content);
+            EXIT_RUNTIME_ERROR
+        }
+    }
+}
+
+fn repl_command(ignore_keyword_case: bool) -> i32 {
+    let mut repl = Repl::new(io::stdin().lock(), io::stdout().lock());
+    repl.keywords.case_insensitive = ignore_keyword_case;
+    if io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() {
+        repl.renderer = Renderer::colored();
+    }
+    match repl.run() {
+        Ok(()) => EXIT_SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            EXIT_IO_ERROR
+        }
+    }
+}
+
+fn keywords(args: &Args) -> Keywords {
+    let mut keywords = Keywords::new();
+    keywords.case_insensitive = args.ignore_keyword_case;
+    keywords
+}
+
+fn lexer(args: &Args, content: &str) -> Lexer {
+    let mut lexer = Lexer::new(content.to_string());
+    lexer.keywords = keywords(args);
+    lexer
+}
+
+// Compile the source, reporting any warnings. Errors are left for `failed`.
+fn compile_source(args: &Args, file_name: &str, content: &str) -> Result<Output, Vec<Diagnostic>> {
+    let options = CompileOptions {
+        keywords: keywords(args),
+        source_name: file_name.to_string(),
+        ..CompileOptions::default()
+    };
+    let output = compile(content, &options)?;
+    report(&output.warnings, file_name, content);
+    Ok(output)
+}
+
+fn failed(args: &Args, diagnostics: &[Diagnostic], file_name: &str, content: &str) -> i32 {
+    report(diagnostics, file_name, content);
+    if !args.quiet {
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        eprintln!("Compiling failed with {} error(s).", errors);
+    }
+    EXIT_COMPILE_ERROR
+}
+
+// Read the program from a file, or from standard input for `-`. Returns the name to use
+// in diagnostics along with the source.
+fn read_source(input: &str) -> io::Result<(String, String)> {
+    if input == "-" {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        Ok(("<stdin>".to_string(), content))
+    } else {
+        Ok((input.to_string(), fs::read_to_string(input)?))
+    }
+}
+
+fn write_output(output: &str, text: &str) -> io::Result<()> {
+    if output == "-" {
+        io::stdout().write_all(text.as_bytes())
+    } else {
+        fs::write(output, text)
+    }
+}
+
+fn report(diagnostics: &[Diagnostic], file_name: &str, content: &str) {
+    // Colour only when a person is reading, and never when NO_COLOR is set.
+    let renderer = if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() {
+        Renderer::colored()
+    } else {
+        Renderer::plain()
+    };
+    for diagnostic in diagnostics.iter() {
+        eprint!("{}", renderer.render(diagnostic, file_name, content));
+    }
 }
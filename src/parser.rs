@@ -1,180 +1,417 @@
+use crate::ast::*;
 use crate::lexer::*;
 use crate::token::*;
-use crate::Emitter;
-use std::process::exit;
+use crate::Diagnostic;
+use crate::Severity;
+use crate::Span;
 
 #[derive(Debug)]
-pub struct Parser<'a> {
+pub struct Parser {
     pub lexer: Lexer,
-    pub emitter: &'a mut Emitter,
     pub cur_token: Option<Token>,
     pub peek_token: Option<Token>,
+    // End of the last token consumed, for closing statement and expression spans.
+    pub prev_end: usize,
     pub symbols: Vec<Token>,
     pub labels_declared: Vec<Token>,
     pub labels_gotoed: Vec<Token>,
+    // Terminators (ELSEIF, ELSE, ENDIF, ENDWHILE, NEXT) of the blocks currently being parsed,
+    // innermost last.
+    pub blocks: Vec<TokenType>,
+    // Errors and warnings, in the order they were found.
+    pub diagnostics: Vec<Diagnostic>,
+    // Start offsets of the tokens the lexer could not produce and that no statement has
+    // recovered past yet. A syntax error after one of them is a consequence of it.
+    pub bad_tokens: Vec<usize>,
 }
 
-impl<'a> Parser<'a> {
+impl Parser {
+    pub fn new(lexer: Lexer) -> Parser {
+        Parser {
+            lexer,
+            cur_token: None,
+            peek_token: None,
+            prev_end: 0,
+            symbols: Vec::new(),
+            labels_declared: Vec::new(),
+            labels_gotoed: Vec::new(),
+            blocks: Vec::new(),
+            diagnostics: Vec::new(),
+            bad_tokens: Vec::new(),
+        }
+    }
     fn check_token(&mut self, token_type: TokenType) -> bool {
         token_type == self.cur_token.clone().unwrap().kind
     }
-    fn check_peek(&mut self, token_type: TokenType) -> bool {
-        token_type == self.peek_token.clone().unwrap().kind
-    }
-    fn match_token(&mut self, token_type: TokenType) {
+    fn match_token(&mut self, token_type: TokenType) -> Result<Token, Diagnostic> {
         if !self.check_token(token_type.clone()) {
-            self.abort(format!(
-                "Expected {:?}, got {:?}",
-                token_type, self.cur_token
-            ))
+            return self.abort(
+                "E0101",
+                format!(
+                    "Expected {:?}, got {:?}",
+                    token_type,
+                    self.cur_token.as_ref().unwrap().kind
+                ),
+            );
         }
+        let token = self.cur_token.clone().unwrap();
         self.next_token();
+        Ok(token)
     }
     fn next_token(&mut self) {
-        self.lexer.next_char();
+        if let Some(token) = self.cur_token.as_ref() {
+            self.prev_end = token.span.end;
+        }
         self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.get_token();
+        // A bad token is reported and skipped, so the parser always sees valid tokens.
+        // Past the end the lexer is exhausted and the peek token stays at EOF.
+        for token in self.lexer.by_ref() {
+            match token {
+                Ok(token) => {
+                    self.peek_token = Some(token);
+                    break;
+                }
+                Err(diagnostic) => {
+                    self.bad_tokens
+                        .extend(diagnostic.span.map(|span| span.start));
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        self.diagnostics.append(&mut self.lexer.diagnostics);
+    }
+    fn abort<T>(&self, code: &'static str, message: String) -> Result<T, Diagnostic> {
+        Err(Diagnostic::error(
+            code,
+            message,
+            self.cur_token.as_ref().map(|token| token.span),
+        ))
+    }
+    // Span from the start of `start` to the end of the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.prev_end.max(start.start),
+            ..start
+        }
+    }
+    // Symbols and labels are compared by name, wherever they appear in the source.
+    fn contains_name(tokens: &[Token], token: &Token) -> bool {
+        tokens.iter().any(|t| t.text == token.text)
+    }
+    // Whether the current token ends the program or one of the enclosing blocks.
+    fn at_block_end(&mut self) -> bool {
+        self.check_token(TokenType::EOF)
+            || self.blocks.contains(&self.cur_token.as_ref().unwrap().kind)
     }
-    fn abort(&self, message: String) {
-        println!("{}", message);
-        exit(0);
+    // A broken IF/WHILE/FOR header is reported but its body is still parsed, so the
+    // block's terminator is not mistaken for a stray statement.
+    fn block_header<T>(&mut self, header: Result<T, Diagnostic>) -> Option<T> {
+        match header {
+            Ok(header) => Some(header),
+            Err(diagnostic) => {
+                self.recover(diagnostic);
+                None
+            }
+        }
+    }
+    // Record a syntax error and resynchronize. The error is dropped when the lexer already
+    // reported a bad token before it, since the missing token is the likely cause.
+    fn recover(&mut self, diagnostic: Diagnostic) {
+        let at = diagnostic.span.map_or(usize::MAX, |span| span.start);
+        if !self.bad_tokens.iter().any(|&bad| bad < at) {
+            self.diagnostics.push(diagnostic);
+        }
+        self.synchronize();
+        self.recovered();
+    }
+    // Forget the bad tokens before the current token, which belong to finished statements.
+    fn recovered(&mut self) {
+        let at = self.cur_token.as_ref().unwrap().span.start;
+        self.bad_tokens.retain(|&bad| bad >= at);
+    }
+    // Parse statements up to one of `terminators`, which is left for the caller, reporting
+    // a missing terminator at `opener`. The last terminator is the one that closes it.
+    fn block(
+        &mut self,
+        terminators: &[TokenType],
+        opener: &Token,
+    ) -> Result<Vec<Stmt>, Diagnostic> {
+        let depth = self.blocks.len();
+        self.blocks.extend_from_slice(terminators);
+        let mut body = Vec::new();
+        while !self.at_block_end() {
+            body.extend(self.statement());
+        }
+        self.blocks.truncate(depth);
+        if self.check_token(TokenType::EOF) {
+            return Err(Diagnostic::error(
+                "E0108",
+                format!(
+                    "Expected {:?} to close this {:?}, got end of file",
+                    terminators.last().unwrap(),
+                    opener.kind
+                ),
+                Some(opener.span),
+            ));
+        }
+        Ok(body)
+    }
+    // Skip the rest of a broken statement: up to the next line, or to a terminator that
+    // closes an enclosing block so that block can still finish.
+    fn synchronize(&mut self) {
+        while !self.check_token(TokenType::NEWLINE) && !self.at_block_end() {
+            self.next_token();
+        }
+        self.nl();
     }
-    pub fn program(&mut self) {
+    pub fn program(&mut self) -> Result<Program, Vec<Diagnostic>> {
         self.next_token();
         self.next_token();
-        self.emitter.header_line("#include <stdio.h>".to_string());
-        self.emitter.header_line("int main(void){".to_string());
         // println!("PROGRAM");
-        while self.check_token(TokenType::NEWLINE) {
-            self.next_token();
-        }
+        self.nl();
+        let mut statements = Vec::new();
         while !self.check_token(TokenType::EOF) {
-            self.statement();
+            statements.extend(self.statement());
         }
 
-        self.emitter.emit_line("return 0;".to_string());
-        self.emitter.emit_line("}".to_string());
-
         for label in self.labels_gotoed.iter() {
-            if !self.labels_declared.contains(label) {
-                self.abort(format!(
-                    "Attempting to GOTO to undeclared label: {:?}",
-                    label
-                ))
+            if !Self::contains_name(&self.labels_declared, label) {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0107",
+                    format!("Attempting to GOTO to undeclared label: {:?}", label.text),
+                    Some(label.span),
+                ));
+            }
+        }
+        // Warnings stay in `diagnostics` for the caller when there are no errors.
+        if !self
+            .diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+        {
+            Ok(Program { statements })
+        } else {
+            Err(std::mem::take(&mut self.diagnostics))
+        }
+    }
+    // Parse one statement. On a syntax error the error is recorded and the parser
+    // resynchronizes, so one compile reports every broken statement.
+    fn statement(&mut self) -> Option<Stmt> {
+        match self.parse_statement() {
+            Ok(stmt) => {
+                self.recovered();
+                stmt
+            }
+            Err(diagnostic) => {
+                self.recover(diagnostic);
+                None
             }
         }
     }
-    fn statement(&mut self) {
-        if self.check_token(TokenType::PRINT) {
+    // Returns Ok(None) for a block whose header was broken; its errors are already recorded.
+    fn parse_statement(&mut self) -> Result<Option<Stmt>, Diagnostic> {
+        let start = self.cur_token.as_ref().unwrap().span;
+        let stmt = if self.check_token(TokenType::PRINT) {
             // println!("---STATEMENT-PRINT");
             self.next_token();
-            if self.check_token(TokenType::STRING) {
-                self.emitter.emit_line(format!(
-                    "printf(\"{}\");",
-                    self.cur_token.as_ref().unwrap().text
-                ));
+            let value = if self.check_token(TokenType::STRING) {
+                let value = self.cur_token.as_ref().unwrap().value.clone();
                 self.next_token();
+                PrintValue::String(value.unwrap_or_default())
             } else {
-                self.emitter
-                    .emit(format!("{}{}", "printf(\"%", ".2f\\n\", (float)("));
-                self.expression();
-                self.emitter.emit_line("));".to_string());
-            }
+                PrintValue::Expr(self.expression()?)
+            };
+            Some(Stmt::Print {
+                value,
+                span: self.span_from(start),
+            })
         } else if self.check_token(TokenType::IF) {
             // println!("---STATEMENT-IF");
+            let opener = self.cur_token.clone().unwrap();
             self.next_token();
-            self.emitter.emit("if(".to_string());
-            self.comparison();
-            self.match_token(TokenType::THEN);
+            let header = self.condition().and_then(|condition| {
+                self.match_token(TokenType::THEN)?;
+                Ok(condition)
+            });
+            let condition = self.block_header(header);
             self.nl();
-            self.emitter.emit_line("){".to_string());
             // zero or more statements in the body
-            while !self.check_token(TokenType::ENDIF) {
-                self.statement();
+            let branch = [TokenType::ELSEIF, TokenType::ELSE, TokenType::ENDIF];
+            let body = self.block(&branch, &opener)?;
+            let mut elseifs = Vec::new();
+            let mut complete = condition.is_some();
+            while self.check_token(TokenType::ELSEIF) {
+                self.next_token();
+                let header = self.condition().and_then(|condition| {
+                    self.match_token(TokenType::THEN)?;
+                    Ok(condition)
+                });
+                let elseif = self.block_header(header);
+                self.nl();
+                let body = self.block(&branch, &opener)?;
+                match elseif {
+                    Some(elseif) => elseifs.push((elseif, body)),
+                    None => complete = false,
+                }
+            }
+            let else_body = if self.check_token(TokenType::ELSE) {
+                self.next_token();
+                self.nl();
+                Some(self.block(&[TokenType::ENDIF], &opener)?)
+            } else {
+                None
+            };
+            self.match_token(TokenType::ENDIF)?;
+            match condition {
+                Some(condition) if complete => Some(Stmt::If {
+                    condition,
+                    body,
+                    elseifs,
+                    else_body,
+                    span: self.span_from(start),
+                }),
+                _ => None,
             }
-            self.match_token(TokenType::ENDIF);
-            self.emitter.emit_line("}".to_string());
         } else if self.check_token(TokenType::WHILE) {
             // println!("---STATEMENT-WHILE");
+            let opener = self.cur_token.clone().unwrap();
             self.next_token();
-            self.emitter.emit("while(".to_string());
-            self.comparison();
+            let header = self.condition().and_then(|condition| {
+                self.match_token(TokenType::REPEAT)?;
+                Ok(condition)
+            });
+            let condition = self.block_header(header);
+            self.nl();
 
-            self.match_token(TokenType::REPEAT);
+            let body = self.block(&[TokenType::ENDWHILE], &opener)?;
+            self.match_token(TokenType::ENDWHILE)?;
+            condition.map(|condition| Stmt::While {
+                condition,
+                body,
+                span: self.span_from(start),
+            })
+        } else if self.check_token(TokenType::FOR) {
+            let opener = self.cur_token.clone().unwrap();
+            self.next_token();
+            let header = self.for_header();
+            let header = self.block_header(header);
             self.nl();
-            self.emitter.emit_line("){".to_string());
 
-            while !self.check_token(TokenType::ENDWHILE) {
-                self.statement();
+            let body = self.block(&[TokenType::NEXT], &opener)?;
+            self.match_token(TokenType::NEXT)?;
+            // The variable after NEXT is optional, but must be the loop's own.
+            if self.check_token(TokenType::IDENT) {
+                let next = self.match_token(TokenType::IDENT)?;
+                if let Some((name, ..)) =
+                    header.as_ref().filter(|(name, ..)| name.text != next.text)
+                {
+                    return Err(Diagnostic::error(
+                        "E0110",
+                        format!("NEXT {:?} does not match FOR {:?}", next.text, name.text),
+                        Some(next.span),
+                    )
+                    .with_note("loop variable declared here".to_string(), Some(name.span)));
+                }
             }
-            self.match_token(TokenType::ENDWHILE);
-            self.emitter.emit_line("}".to_string());
+            let span = self.span_from(start);
+            header.map(|(name, start, end, step)| Stmt::For {
+                name: name.text,
+                start,
+                end,
+                step,
+                body,
+                span,
+            })
         } else if self.check_token(TokenType::LABEL) {
             // println!("---STATEMENT-LABEL");
             self.next_token();
 
-            if self
-                .labels_declared
-                .contains(&self.cur_token.as_ref().unwrap())
-            {
-                self.abort(format!(
-                    "Label already exists: {:?}",
-                    self.cur_token.as_ref().unwrap().text
-                ))
+            let label = self.cur_token.as_ref().unwrap();
+            if let Some(first) = self.labels_declared.iter().find(|t| t.text == label.text) {
+                return Err(Diagnostic::error(
+                    "E0106",
+                    format!("Label already exists: {:?}", label.text),
+                    Some(label.span),
+                )
+                .with_note("label first declared here".to_string(), Some(first.span)));
             }
-            self.labels_declared.push(self.cur_token.clone().unwrap());
-
-            self.emitter
-                .emit_line(format!("{}:", self.cur_token.as_ref().unwrap().text));
-
-            self.match_token(TokenType::IDENT);
+            let label = self.match_token(TokenType::IDENT)?;
+            self.labels_declared.push(label.clone());
+            Some(Stmt::Label {
+                name: label.text,
+                span: self.span_from(start),
+            })
         } else if self.check_token(TokenType::GOTO) {
             // println!("---STATEMENT-GOTO");
             self.next_token();
-            self.labels_gotoed.push(self.cur_token.clone().unwrap());
-            self.emitter
-                .emit_line(format!("goto {};", self.cur_token.as_ref().unwrap().text));
-            self.match_token(TokenType::IDENT);
+            let label = self.match_token(TokenType::IDENT)?;
+            self.labels_gotoed.push(label.clone());
+            Some(Stmt::Goto {
+                name: label.text,
+                span: self.span_from(start),
+            })
         } else if self.check_token(TokenType::LET) {
             // println!("---STATEMENT-LET");
             self.next_token();
-            if !self.symbols.contains(self.cur_token.as_ref().unwrap()) {
-                self.symbols.push(self.cur_token.clone().unwrap());
-                self.emitter
-                    .header_line(format!("float {};", self.cur_token.as_ref().unwrap().text));
+            let name = self.match_token(TokenType::IDENT)?;
+            if !Self::contains_name(&self.symbols, &name) {
+                self.symbols.push(name.clone());
             }
-            self.emitter
-                .emit(format!("{} = ", self.cur_token.as_ref().unwrap().text));
-            self.match_token(TokenType::IDENT);
-            self.match_token(TokenType::EQ);
-            self.expression();
-            self.emitter.emit_line(";".to_string());
+            self.match_token(TokenType::EQ)?;
+            let value = self.expression()?;
+            Some(Stmt::Let {
+                name: name.text,
+                value,
+                span: self.span_from(start),
+            })
         } else if self.check_token(TokenType::INPUT) {
             // println!("---STATEMENT-INPUT");
             self.next_token();
-            if !self.symbols.contains(self.cur_token.as_ref().unwrap()) {
-                self.symbols.push(self.cur_token.clone().unwrap());
-                self.emitter
-                    .header_line(format!("float {};", self.cur_token.as_ref().unwrap().text));
+            let name = self.match_token(TokenType::IDENT)?;
+            if !Self::contains_name(&self.symbols, &name) {
+                self.symbols.push(name.clone());
             }
-            self.emitter.emit_line(format!(
-                "if(0 == scanf(\"%f\", &{})) {{",
-                self.cur_token.as_ref().unwrap().text
-            ));
-            self.emitter
-                .emit_line(format!("{} = 0;", self.cur_token.as_ref().unwrap().text));
-            self.emitter.emit("scanf(\"%".to_string());
-            self.emitter.emit_line("*s\");".to_string());
-            self.emitter.emit_line("}".to_string());
-            self.match_token(TokenType::IDENT);
+            Some(Stmt::Input {
+                name: name.text,
+                span: self.span_from(start),
+            })
         } else {
-            self.abort(format!(
-                "Invalid statement at {:?} ({:?})",
-                self.cur_token.clone().unwrap().text,
-                self.cur_token.clone().unwrap().kind
-            ))
-        }
+            let token = self.cur_token.clone().unwrap();
+            let mut diagnostic = Diagnostic::error(
+                "E0102",
+                format!("Invalid statement at {:?} ({:?})", token.text, token.kind),
+                Some(token.span),
+            );
+            // `print` where PRINT was meant.
+            let upper = token.text.to_uppercase();
+            if token.kind == TokenType::IDENT && self.lexer.keywords.words.contains_key(&upper) {
+                diagnostic = diagnostic.with_note(
+                    format!("keywords are case-sensitive, did you mean {}?", upper),
+                    None,
+                );
+            }
+            return Err(diagnostic);
+        };
         self.nl();
+        Ok(stmt)
+    }
+    // name = start TO end [STEP step], declaring the loop variable like LET does.
+    fn for_header(&mut self) -> Result<(Token, Expr, Expr, Option<Expr>), Diagnostic> {
+        let name = self.match_token(TokenType::IDENT)?;
+        if !Self::contains_name(&self.symbols, &name) {
+            self.symbols.push(name.clone());
+        }
+        self.match_token(TokenType::EQ)?;
+        let start = self.expression()?;
+        self.match_token(TokenType::TO)?;
+        let end = self.expression()?;
+        let step = if self.check_token(TokenType::STEP) {
+            self.next_token();
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        Ok((name, start, end, step))
     }
     fn nl(&mut self) {
         // println!("---NEWLINE");
@@ -182,85 +419,267 @@ impl<'a> Parser<'a> {
             self.next_token();
         }
     }
-    fn comparison(&mut self) {
-        // println!("---COMPARISON");
-        self.expression();
-        // Must be at least one comparison operator and another expression.
-        if self.is_comparison_operator() {
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
+    // condition ::= and {OR and}, and ::= not {AND not}, not ::= NOT not | comparison
+    fn condition(&mut self) -> Result<Condition, Diagnostic> {
+        // println!("---CONDITION");
+        match self.logical(1)? {
+            Operand::Condition(condition) => Ok(condition),
+            Operand::Expr(_) => self.expected_comparison(),
+        }
+    }
+    // Parse conditions joined by operators of at least `precedence`. A lone expression is
+    // passed back so that `(` can also open an arithmetic group, as in `(a + b) * 2 < c`.
+    fn logical(&mut self, precedence: u8) -> Result<Operand, Diagnostic> {
+        if precedence > LogicalOp::And.precedence() {
+            return self.not_condition();
+        }
+        let mut left = self.logical(precedence + 1)?;
+        loop {
+            let op = if self.check_token(TokenType::OR) {
+                LogicalOp::Or
+            } else if self.check_token(TokenType::AND) {
+                LogicalOp::And
+            } else {
+                break;
+            };
+            if op.precedence() != precedence {
+                break;
+            }
+            let left_condition = self.operand_condition(left)?;
+            self.next_token();
+            let right = self.logical(precedence + 1)?;
+            let right = self.operand_condition(right)?;
+            left = Operand::Condition(Condition::Logical {
+                op,
+                span: self.span_from(left_condition.span()),
+                left: Box::new(left_condition),
+                right: Box::new(right),
+            });
+        }
+        Ok(left)
+    }
+    fn not_condition(&mut self) -> Result<Operand, Diagnostic> {
+        let start = self.cur_token.as_ref().unwrap().span;
+        if self.check_token(TokenType::NOT) {
             self.next_token();
-            self.expression();
+            let operand = self.not_condition()?;
+            let operand = self.operand_condition(operand)?;
+            return Ok(Operand::Condition(Condition::Not {
+                operand: Box::new(operand),
+                span: self.span_from(start),
+            }));
+        }
+        let left = if self.check_token(TokenType::LPAREN) {
+            self.next_token();
+            let inner = self.logical(1)?;
+            self.match_token(TokenType::RPAREN)?;
+            let span = self.span_from(start);
+            match inner {
+                Operand::Condition(inner) => {
+                    return Ok(Operand::Condition(Condition::Group {
+                        inner: Box::new(inner),
+                        span,
+                    }))
+                }
+                // An arithmetic group: carry on with the rest of the expression.
+                Operand::Expr(inner) => {
+                    let group = Expr::Group {
+                        inner: Box::new(inner),
+                        span,
+                    };
+                    let power = self.power_rest(group)?;
+                    let term = self.term_rest(power)?;
+                    self.expression_rest(term)?
+                }
+            }
         } else {
-            self.abort(format!(
-                "Expected comparison operator at: {:?}",
-                self.cur_token.as_ref().unwrap().text,
+            self.expression()?
+        };
+        let op = match self.comparison_operator() {
+            Some(op) => op,
+            None => return Ok(Operand::Expr(left)),
+        };
+        self.next_token();
+        let right = self.expression()?;
+        if self.comparison_operator().is_some() {
+            return Err(Diagnostic::error(
+                "E0109",
+                "Comparisons cannot be chained".to_string(),
+                self.cur_token.as_ref().map(|token| token.span),
+            )
+            .with_note(
+                "compare each pair and join them with AND, as in `a < b AND b < c`".to_string(),
+                None,
             ));
         }
-
-        // Can have 0 or more comparison operator and expressions.
-        while self.is_comparison_operator() {
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
-            self.next_token();
-            self.expression();
+        Ok(Operand::Condition(Condition::Compare {
+            op,
+            span: self.span_from(left.span()),
+            left,
+            right,
+        }))
+    }
+    // The operands of AND, OR and NOT must be conditions, not bare expressions.
+    fn operand_condition(&self, operand: Operand) -> Result<Condition, Diagnostic> {
+        match operand {
+            Operand::Condition(condition) => Ok(condition),
+            Operand::Expr(_) => self.expected_comparison(),
         }
     }
-    fn is_comparison_operator(&mut self) -> bool {
-        self.check_token(TokenType::GT)
-            || self.check_token(TokenType::GTEQ)
-            || self.check_token(TokenType::LT)
-            || self.check_token(TokenType::LTEQ)
-            || self.check_token(TokenType::EQEQ)
-            || self.check_token(TokenType::NOTEQ)
+    fn expected_comparison<T>(&self) -> Result<T, Diagnostic> {
+        self.abort(
+            "E0103",
+            format!(
+                "Expected comparison operator at: {:?}",
+                self.cur_token.as_ref().unwrap().text,
+            ),
+        )
     }
-    fn expression(&mut self) {
+    fn comparison_operator(&self) -> Option<CompareOp> {
+        match self.cur_token.as_ref().unwrap().kind {
+            TokenType::GT => Some(CompareOp::Gt),
+            TokenType::GTEQ => Some(CompareOp::GtEq),
+            TokenType::LT => Some(CompareOp::Lt),
+            TokenType::LTEQ => Some(CompareOp::LtEq),
+            TokenType::EQEQ => Some(CompareOp::Eq),
+            TokenType::NOTEQ => Some(CompareOp::NotEq),
+            _ => None,
+        }
+    }
+    fn expression(&mut self) -> Result<Expr, Diagnostic> {
         // println!("---EXPRESSION");
-        self.term();
+        let left = self.term()?;
+        self.expression_rest(left)
+    }
+    // The +/- operations following the first term.
+    fn expression_rest(&mut self, mut left: Expr) -> Result<Expr, Diagnostic> {
         // Can have 0 or more +/- and expressions.
-        while self.check_token(TokenType::PLUS) || self.check_token(TokenType::MINUS) {
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
+        loop {
+            let op = if self.check_token(TokenType::PLUS) {
+                BinaryOp::Add
+            } else if self.check_token(TokenType::MINUS) {
+                BinaryOp::Sub
+            } else {
+                break;
+            };
             self.next_token();
-            self.term();
+            let right = self.term()?;
+            left = Expr::Binary {
+                op,
+                span: self.span_from(left.span()),
+                left: Box::new(left),
+                right: Box::new(right),
+            };
         }
+        Ok(left)
     }
-    fn term(&mut self) {
+    fn term(&mut self) -> Result<Expr, Diagnostic> {
         // println!("---TERM");
-        self.unary();
+        let left = self.unary()?;
+        self.term_rest(left)
+    }
+    // The *, /, %, MOD and \ operations following the first unary.
+    fn term_rest(&mut self, mut left: Expr) -> Result<Expr, Diagnostic> {
         // Can have 0 or more *// and expressions.
-        while self.check_token(TokenType::ASTERISK) || self.check_token(TokenType::SLASH) {
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
+        loop {
+            let op = if self.check_token(TokenType::ASTERISK) {
+                BinaryOp::Mul
+            } else if self.check_token(TokenType::SLASH) {
+                BinaryOp::Div
+            } else if self.check_token(TokenType::PERCENT) || self.check_token(TokenType::MOD) {
+                BinaryOp::Mod
+            } else if self.check_token(TokenType::BACKSLASH) {
+                BinaryOp::IntDiv
+            } else {
+                break;
+            };
             self.next_token();
-            self.unary();
+            let right = self.unary()?;
+            left = Expr::Binary {
+                op,
+                span: self.span_from(left.span()),
+                left: Box::new(left),
+                right: Box::new(right),
+            };
         }
+        Ok(left)
     }
-    fn unary(&mut self) {
+    fn unary(&mut self) -> Result<Expr, Diagnostic> {
         // println!("---UNARY");
         // Optional unary +/-
-        if self.check_token(TokenType::PLUS) || self.check_token(TokenType::MINUS) {
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
-            self.next_token();
+        let start = self.cur_token.as_ref().unwrap().span;
+        let op = if self.check_token(TokenType::PLUS) {
+            UnaryOp::Plus
+        } else if self.check_token(TokenType::MINUS) {
+            UnaryOp::Minus
+        } else {
+            return self.power();
+        };
+        self.next_token();
+        let operand = self.power()?;
+        Ok(Expr::Unary {
+            op,
+            operand: Box::new(operand),
+            span: self.span_from(start),
+        })
+    }
+    fn power(&mut self) -> Result<Expr, Diagnostic> {
+        let base = self.primary()?;
+        self.power_rest(base)
+    }
+    // An optional ^ after the base. The exponent is a unary, so `^` is right-associative
+    // and `2 ^ -1` needs no parentheses.
+    fn power_rest(&mut self, base: Expr) -> Result<Expr, Diagnostic> {
+        if !self.check_token(TokenType::CARET) {
+            return Ok(base);
         }
-        self.primary();
+        self.next_token();
+        let exponent = self.unary()?;
+        Ok(Expr::Binary {
+            op: BinaryOp::Pow,
+            span: self.span_from(base.span()),
+            left: Box::new(base),
+            right: Box::new(exponent),
+        })
     }
-    fn primary(&mut self) {
+    fn primary(&mut self) -> Result<Expr, Diagnostic> {
         // println!("---PRIMARY");
+        let token = self.cur_token.clone().unwrap();
         if self.check_token(TokenType::NUMBER) {
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
             self.next_token();
+            Ok(Expr::Number {
+                text: token.value.unwrap_or(token.text),
+                span: token.span,
+            })
         } else if self.check_token(TokenType::IDENT) {
-            if !self.symbols.contains(self.cur_token.as_ref().unwrap()) {
-                self.abort(format!(
-                    "Referencing variable before assignment: {:?}",
-                    self.cur_token.as_ref().unwrap().text
-                ))
+            if !Self::contains_name(&self.symbols, &token) {
+                return self.abort(
+                    "E0105",
+                    format!("Referencing variable before assignment: {:?}", token.text),
+                );
             }
-            self.emitter.emit(self.cur_token.clone().unwrap().text);
             self.next_token();
+            Ok(Expr::Var {
+                name: token.text,
+                span: token.span,
+            })
+        } else if self.check_token(TokenType::LPAREN) {
+            self.next_token();
+            let inner = self.expression()?;
+            self.match_token(TokenType::RPAREN)?;
+            Ok(Expr::Group {
+                inner: Box::new(inner),
+                span: self.span_from(token.span),
+            })
         } else {
             // Error!
-            self.abort(format!(
-                "Unexpected token at {:?}",
-                self.cur_token.as_ref().unwrap().text
-            ));
+            self.abort("E0104", format!("Unexpected token at {:?}", token.text))
         }
     }
 }
+
+// Inside parentheses in a condition, either a condition or an arithmetic expression.
+enum Operand {
+    Condition(Condition),
+    Expr(Expr),
+}
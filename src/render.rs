@@ -0,0 +1,133 @@
+use crate::Diagnostic;
+use crate::Severity;
+use crate::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const GREEN: &str = "\x1b[1;32m";
+
+// Renders diagnostics rustc-style: a header, the location, the offending source line and
+// a caret underline below the span, followed by any notes.
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    pub color: bool,
+}
+
+impl Renderer {
+    pub fn plain() -> Renderer {
+        Renderer { color: false }
+    }
+
+    pub fn colored() -> Renderer {
+        Renderer { color: true }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn severity_style(severity: Severity) -> &'static str {
+        match severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic, file_name: &str, source: &str) -> String {
+        let style = Self::severity_style(diagnostic.severity);
+        let mut out = format!(
+            "{}{}\n",
+            self.paint(
+                style,
+                &format!("{}[{}]", diagnostic.severity, diagnostic.code)
+            ),
+            self.paint(BOLD, &format!(": {}", diagnostic.message)),
+        );
+
+        // Every snippet shares one gutter width so the bars line up.
+        let gutter = std::iter::once(diagnostic.span)
+            .chain(diagnostic.notes.iter().map(|note| note.span))
+            .flatten()
+            .map(|span| span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        if let Some(span) = diagnostic.span {
+            out.push_str(&self.snippet(span, '^', style, gutter, file_name, source));
+        }
+        for note in diagnostic.notes.iter() {
+            out.push_str(&format!(
+                "{}: {}\n",
+                self.paint(GREEN, "note"),
+                note.message
+            ));
+            if let Some(span) = note.span {
+                out.push_str(&self.snippet(span, '-', BLUE, gutter, file_name, source));
+            }
+        }
+        out
+    }
+
+    fn snippet(
+        &self,
+        span: Span,
+        marker: char,
+        style: &str,
+        gutter: usize,
+        file_name: &str,
+        source: &str,
+    ) -> String {
+        let pad = " ".repeat(gutter);
+        let bar = self.paint(BLUE, "|");
+        let mut out = format!(
+            "{}{} {}:{}:{}\n",
+            pad,
+            self.paint(BLUE, "-->"),
+            file_name,
+            span.line,
+            span.column
+        );
+
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+
+        // Keep tabs in the indent so the carets line up with the source line.
+        let indent: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = source[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let underline: String = std::iter::repeat_n(marker, width).collect();
+
+        out.push_str(&format!("{} {}\n", pad, bar));
+        out.push_str(&format!(
+            "{} {} {}\n",
+            self.paint(BLUE, &format!("{:>width$}", span.line, width = gutter)),
+            bar,
+            line
+        ));
+        out.push_str(&format!(
+            "{} {} {}{}\n",
+            pad,
+            bar,
+            indent,
+            self.paint(style, &underline)
+        ));
+        out
+    }
+}
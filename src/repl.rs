@@ -0,0 +1,166 @@
+use crate::ast::Program;
+use crate::codegen::CodeGen;
+use crate::interpreter::Interpreter;
+use crate::Diagnostic;
+use crate::Emitter;
+use crate::Keywords;
+use crate::Lexer;
+use crate::Parser;
+use crate::Renderer;
+use crate::Severity;
+use crate::Token;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Enter statements to run them. IF, WHILE and FOR blocks continue until their ENDIF/ENDWHILE/NEXT.
+  :tokens <code>  Show how the code is lexed
+  :ast <code>     Show how the code is parsed
+  :c <code>       Show the C generated for the code
+  :help           Show this help
+  :quit           Leave (end of input works too)
+";
+
+// Reads statements line by line and runs each entry as soon as it is complete, keeping
+// the symbol table and variable values from one entry to the next.
+#[derive(Debug)]
+pub struct Repl<R: BufRead, W: Write> {
+    // Runs entries; its input also feeds INPUT statements, and PRINT shares the output.
+    pub interpreter: Interpreter<R, W>,
+    // Variables declared by earlier entries, as `Parser::symbols`.
+    pub symbols: Vec<Token>,
+    // The keywords entries are lexed with, for :tokens as well.
+    pub keywords: Keywords,
+    pub renderer: Renderer,
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    pub fn new(input: R, output: W) -> Repl<R, W> {
+        Repl {
+            interpreter: Interpreter::new(input, output),
+            symbols: Vec::new(),
+            keywords: Keywords::new(),
+            renderer: Renderer::plain(),
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut entry = String::new();
+        loop {
+            let prompt = if entry.is_empty() { "> " } else { ".. " };
+            write!(self.interpreter.output, "{}", prompt)?;
+            self.interpreter.output.flush()?;
+
+            let mut line = String::new();
+            if self.interpreter.input.read_line(&mut line)? == 0 {
+                writeln!(self.interpreter.output)?;
+                return Ok(());
+            }
+            if entry.is_empty() {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                if let Some(command) = trimmed.strip_prefix(':') {
+                    if !self.meta_command(command)? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            }
+
+            entry.push_str(&line);
+            if !line.ends_with('\n') {
+                entry.push('\n');
+            }
+            match self.parse(&entry) {
+                Ok((program, symbols, warnings)) => {
+                    self.report(&warnings, &entry)?;
+                    self.symbols = symbols;
+                    if let Err(diagnostic) = self.interpreter.run(&program) {
+                        self.report(&[diagnostic], &entry)?;
+                    }
+                }
+                // Only unclosed blocks or block comments: keep reading lines into the same entry.
+                Err(diagnostics)
+                    if diagnostics
+                        .iter()
+                        .filter(|d| d.severity == Severity::Error)
+                        .all(|d| d.code == "E0108" || d.code == "E0006") =>
+                {
+                    continue
+                }
+                Err(diagnostics) => self.report(&diagnostics, &entry)?,
+            }
+            entry.clear();
+        }
+    }
+
+    // Handle `:command argument`. Returns false when the REPL should stop.
+    fn meta_command(&mut self, command: &str) -> io::Result<bool> {
+        let (name, code) = command.split_once(' ').unwrap_or((command, ""));
+        let code = format!("{}\n", code.trim());
+        match name {
+            "quit" | "q" => return Ok(false),
+            "help" | "h" => write!(self.interpreter.output, "{}", HELP)?,
+            "tokens" => match crate::lex(self.lexer(&code)) {
+                Ok(tokens) => {
+                    for token in tokens.iter() {
+                        writeln!(self.interpreter.output, "{:?} {:?}", token.kind, token.text)?;
+                    }
+                }
+                Err(diagnostics) => self.report(&diagnostics, &code)?,
+            },
+            "ast" => match self.parse(&code) {
+                Ok((program, _, _)) => writeln!(self.interpreter.output, "{:#?}", program)?,
+                Err(diagnostics) => self.report(&diagnostics, &code)?,
+            },
+            "c" => match self.parse(&code) {
+                Ok((program, _, _)) => {
+                    let mut emitter = Emitter::new(String::new());
+                    let mut codegen = CodeGen::new(&mut emitter);
+                    codegen.line_directives = false;
+                    // Earlier entries declared their own variables, so only new ones appear.
+                    codegen.declared = self.symbols.iter().map(|t| t.text.clone()).collect();
+                    codegen.program(&program);
+                    write!(self.interpreter.output, "{}", emitter.output())?;
+                }
+                Err(diagnostics) => self.report(&diagnostics, &code)?,
+            },
+            _ => writeln!(
+                self.interpreter.output,
+                "Unknown command :{}, try :help",
+                name
+            )?,
+        }
+        Ok(true)
+    }
+
+    // Parse an entry against the variables declared so far, returning the program, the
+    // symbol table that includes its declarations and any warnings.
+    fn parse(
+        &self,
+        source: &str,
+    ) -> Result<(Program, Vec<Token>, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let mut parser = Parser::new(self.lexer(source));
+        parser.symbols = self.symbols.clone();
+        let program = parser.program()?;
+        Ok((program, parser.symbols, parser.diagnostics))
+    }
+
+    fn lexer(&self, source: &str) -> Lexer {
+        let mut lexer = Lexer::new(source.to_string());
+        lexer.keywords = self.keywords.clone();
+        lexer
+    }
+
+    fn report(&mut self, diagnostics: &[Diagnostic], source: &str) -> io::Result<()> {
+        for diagnostic in diagnostics.iter() {
+            write!(
+                self.interpreter.output,
+                "{}",
+                self.renderer.render(diagnostic, "<repl>", source)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,28 +1,123 @@
+use crate::Span;
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub text: String,
     pub kind: TokenType,
+    pub span: Span,
+    // The value of a literal, where `text` keeps it as written: a STRING with its escape
+    // sequences decoded, or a NUMBER spelled for C.
+    pub value: Option<String>,
+    // The whitespace and comments just before the token, when the lexer keeps trivia.
+    pub leading: Vec<Trivia>,
 }
 
 impl Token {
-    pub fn check_if_keyword(input: &str) -> Option<TokenType> {
-        match input {
-            "LABEL" => return Some(TokenType::LABEL),
-            "GOTO" => return Some(TokenType::GOTO),
-            "PRINT" => return Some(TokenType::PRINT),
-            "INPUT" => return Some(TokenType::INPUT),
-            "LET" => return Some(TokenType::LET),
-            "IF" => return Some(TokenType::IF),
-            "THEN" => return Some(TokenType::THEN),
-            "ENDIF" => return Some(TokenType::ENDIF),
-            "WHILE" => return Some(TokenType::WHILE),
-            "REPEAT" => return Some(TokenType::REPEAT),
-            "ENDWHILE" => return Some(TokenType::ENDWHILE),
-            _ => return None,
-        };
+    // The token as it appears in the source, after its leading trivia.
+    pub fn source(&self) -> String {
+        let mut source: String = self.leading.iter().map(|t| t.text.as_str()).collect();
+        match self.kind {
+            TokenType::STRING => {
+                source.push('"');
+                source.push_str(&self.text);
+                source.push('"');
+            }
+            TokenType::EOF => {}
+            _ => source.push_str(&self.text),
+        }
+        source
     }
 }
 
+// The built-in keywords.
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("LABEL", TokenType::LABEL),
+    ("GOTO", TokenType::GOTO),
+    ("PRINT", TokenType::PRINT),
+    ("INPUT", TokenType::INPUT),
+    ("LET", TokenType::LET),
+    ("IF", TokenType::IF),
+    ("THEN", TokenType::THEN),
+    ("ELSEIF", TokenType::ELSEIF),
+    ("ELSE", TokenType::ELSE),
+    ("ENDIF", TokenType::ENDIF),
+    ("WHILE", TokenType::WHILE),
+    ("REPEAT", TokenType::REPEAT),
+    ("ENDWHILE", TokenType::ENDWHILE),
+    ("FOR", TokenType::FOR),
+    ("TO", TokenType::TO),
+    ("STEP", TokenType::STEP),
+    ("NEXT", TokenType::NEXT),
+    ("AND", TokenType::AND),
+    ("OR", TokenType::OR),
+    ("NOT", TokenType::NOT),
+    ("MOD", TokenType::MOD),
+];
+
+// The words the lexer turns into keyword tokens. Starts with the built-in keywords, and a
+// dialect can add its own spellings, e.g. `keywords.insert("WEND", TokenType::ENDWHILE)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keywords {
+    // Keyed by the upper-case spelling.
+    pub words: HashMap<String, TokenType>,
+    // Match keywords in any letter case, so `print` and `Print` are PRINT as well.
+    pub case_insensitive: bool,
+}
+
+impl Keywords {
+    pub fn new() -> Keywords {
+        Keywords {
+            words: KEYWORDS
+                .iter()
+                .map(|(word, kind)| (word.to_string(), kind.clone()))
+                .collect(),
+            case_insensitive: false,
+        }
+    }
+
+    // Add or replace a keyword. Keywords are upper case, so the ASCII letters in `word` are
+    // upper-cased.
+    pub fn insert(&mut self, word: &str, kind: TokenType) {
+        self.words.insert(word.to_ascii_uppercase(), kind);
+    }
+
+    // Only ASCII letters fold when ignoring case. Unicode case mapping would turn names
+    // like `ſtep` or `prınt` into keywords.
+    pub fn lookup(&self, word: &str) -> Option<TokenType> {
+        match self.words.get(word) {
+            Some(kind) => Some(kind.clone()),
+            None if self.case_insensitive => self.words.get(&word.to_ascii_uppercase()).cloned(),
+            None => None,
+        }
+    }
+}
+
+impl Default for Keywords {
+    fn default() -> Keywords {
+        Keywords::new()
+    }
+}
+
+// Source text between tokens that does not affect the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    // Spaces, tabs and carriage returns; newlines are NEWLINE tokens.
+    Whitespace,
+    // `#` to the end of the line.
+    LineComment,
+    // `#[ ... ]#`, possibly nested.
+    BlockComment,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenType {
     EOF,
@@ -38,20 +133,35 @@ pub enum TokenType {
     LET,
     IF,
     THEN,
+    ELSEIF,
+    ELSE,
     ENDIF,
     WHILE,
     REPEAT,
     ENDWHILE,
+    FOR,
+    TO,
+    STEP,
+    NEXT,
+    AND,
+    OR,
+    NOT,
+    MOD,
     // Operators
     EQ,
     PLUS,
     MINUS,
     ASTERISK,
     SLASH,
+    PERCENT,
+    BACKSLASH,
+    CARET,
     EQEQ,
     NOTEQ,
     LT,
     LTEQ,
     GT,
     GTEQ,
+    LPAREN,
+    RPAREN,
 }
@@ -0,0 +1,178 @@
+use crate::Diagnostic;
+use crate::Output;
+use crate::Span;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The system C compiler used to turn generated C into a native executable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    pub cc: String,
+    pub flags: Vec<String>,
+}
+
+impl Toolchain {
+    // `$CC` (default `cc`) with the whitespace-separated flags from `$CFLAGS`.
+    pub fn from_env() -> Toolchain {
+        Toolchain {
+            cc: env::var("CC")
+                .ok()
+                .filter(|cc| !cc.is_empty())
+                .unwrap_or_else(|| "cc".to_string()),
+            flags: env::var("CFLAGS")
+                .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    // Compile `output` into an executable at `exe`. C compiler errors come back as
+    // diagnostics pointing at the Teeny Tiny lines named by the `#line` directives, which
+    // must call the source `source_name`.
+    pub fn build(
+        &self,
+        output: &Output,
+        source_name: &str,
+        source: &str,
+        exe: &Path,
+    ) -> Result<(), Vec<Diagnostic>> {
+        let dir = scratch_dir().map_err(|err| {
+            vec![Diagnostic::error(
+                "E0201",
+                format!("Could not create a temporary directory: {}", err),
+                None,
+            )]
+        })?;
+        let result = self.build_in(&dir, output, source_name, source, exe);
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    fn build_in(
+        &self,
+        dir: &Path,
+        output: &Output,
+        source_name: &str,
+        source: &str,
+        exe: &Path,
+    ) -> Result<(), Vec<Diagnostic>> {
+        let c_path = dir.join("out.c");
+        fs::write(&c_path, &output.c_code).map_err(|err| {
+            vec![Diagnostic::error(
+                "E0201",
+                format!("Could not write {}: {}", c_path.display(), err),
+                None,
+            )]
+        })?;
+
+        let mut command = Command::new(&self.cc);
+        command.args(&self.flags).arg(&c_path).arg("-o").arg(exe);
+        if output.needs_libm {
+            command.arg("-lm");
+        }
+        let result = command.output().map_err(|err| {
+            vec![Diagnostic::error(
+                "E0201",
+                format!("Could not run the C compiler {:?}: {}", self.cc, err),
+                None,
+            )]
+        })?;
+        if result.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        Err(c_diagnostics(&stderr, result.status, source_name, source))
+    }
+}
+
+// The diagnostics for a failed C compile: one per error the C compiler reported against
+// a line of the Teeny Tiny source. Its whole output comes as well, as a single E0202, when
+// it also reported something else, such as an error in a header or from the linker.
+pub fn c_diagnostics(
+    stderr: &str,
+    status: impl fmt::Display,
+    source_name: &str,
+    source: &str,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut elsewhere = false;
+    for (location, message) in stderr.lines().filter_map(c_error) {
+        match source_line(location, source_name) {
+            Some(line_number) => diagnostics.push(Diagnostic::error(
+                "E0202",
+                format!("C compiler: {}", message),
+                line_span(source, line_number),
+            )),
+            None => elsewhere = true,
+        }
+    }
+    if elsewhere || diagnostics.is_empty() {
+        diagnostics.push(Diagnostic::error(
+            "E0202",
+            format!("The C compiler failed ({}):\n{}", status, stderr.trim_end()),
+            None,
+        ));
+    }
+    diagnostics
+}
+
+// Split a `location: error: message` line from the C compiler, or a `fatal error:` one,
+// into its location and message.
+fn c_error(line: &str) -> Option<(&str, &str)> {
+    line.split_once(": error: ")
+        .or_else(|| line.split_once(": fatal error: "))
+}
+
+// The line number in a `file:line:column` or `file:line` location, if the file is the
+// Teeny Tiny source that the `#line` directives name.
+fn source_line(location: &str, source_name: &str) -> Option<usize> {
+    let position = location.strip_prefix(source_name)?.strip_prefix(':')?;
+    position.split(':').next()?.parse().ok()
+}
+
+// Span over the text of a 1-based source line, without its indentation.
+fn line_span(source: &str, line_number: usize) -> Option<Span> {
+    let mut start = 0;
+    for (index, line) in source.split('\n').enumerate() {
+        if index + 1 == line_number {
+            let indent = line.len() - line.trim_start().len();
+            return Some(Span {
+                start: start + indent,
+                end: start + line.trim_end().len().max(indent),
+                line: line_number,
+                column: line[..indent].chars().count() + 1,
+            });
+        }
+        start += line.len() + 1;
+    }
+    None
+}
+
+// A fresh, empty directory under the system temp dir. It is created here rather than
+// reused, so nobody else can have put files in it, and a name that is taken is skipped.
+pub fn scratch_dir() -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or(0);
+        let dir = env::temp_dir().join(format!(
+            "teeny-tiny-{}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            nanos
+        ));
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
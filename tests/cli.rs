@@ -0,0 +1,227 @@
+use rust_basic_compiler::cli::*;
+use std::path::PathBuf;
+
+fn parse(args: &[&str]) -> Result<Command, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+}
+
+fn args(input: &str) -> Args {
+    Args {
+        input: input.to_string(),
+        output: None,
+        emit: Emit::C,
+        quiet: false,
+        cc: None,
+        cflags: Vec::new(),
+        ignore_keyword_case: false,
+    }
+}
+
+#[test]
+fn compiles_by_default() {
+    assert_eq!(parse(&["prog.tt"]), Ok(Command::Compile(args("prog.tt"))));
+    assert_eq!(
+        parse(&["compile", "prog.tt"]),
+        Ok(Command::Compile(args("prog.tt")))
+    );
+    assert_eq!(
+        parse(&["run", "prog.tt"]),
+        Ok(Command::Run(args("prog.tt")))
+    );
+    assert_eq!(
+        parse(&["interpret", "prog.tt"]),
+        Ok(Command::Interpret(args("prog.tt")))
+    );
+    // A command is only recognised before the options and the input.
+    assert_eq!(
+        parse(&["prog.tt", "build"]),
+        Err("unexpected argument \"build\"".to_string())
+    );
+}
+
+#[test]
+fn options_take_values_inline_or_separately() {
+    let expected = Args {
+        output: Some("out.txt".to_string()),
+        emit: Emit::Tokens,
+        cc: Some("clang".to_string()),
+        cflags: vec!["-O2".to_string(), "-Wall".to_string()],
+        quiet: true,
+        ignore_keyword_case: true,
+        ..args("prog.tt")
+    };
+    assert_eq!(
+        parse(&[
+            "build",
+            "--emit=tokens",
+            "-o",
+            "out.txt",
+            "--cc=clang",
+            "--cflag",
+            "-O2",
+            "--cflag=-Wall",
+            "-q",
+            "--ignore-keyword-case",
+            "prog.tt",
+        ]),
+        Ok(Command::Build(expected.clone()))
+    );
+    assert_eq!(
+        parse(&[
+            "build",
+            "--emit",
+            "tokens",
+            "--output=out.txt",
+            "--cc",
+            "clang",
+            "--cflag=-O2",
+            "--cflag",
+            "-Wall",
+            "prog.tt",
+            "--quiet",
+            "--ignore-keyword-case",
+        ]),
+        Ok(Command::Build(expected))
+    );
+    // An inline value is split off at the first `=` only.
+    assert_eq!(
+        parse(&["--cflag=-DX=1", "prog.tt"]),
+        Ok(Command::Compile(Args {
+            cflags: vec!["-DX=1".to_string()],
+            ..args("prog.tt")
+        }))
+    );
+}
+
+#[test]
+fn reports_bad_arguments() {
+    assert_eq!(
+        parse(&["prog.tt", "-o"]),
+        Err("-o needs a value".to_string())
+    );
+    assert_eq!(
+        parse(&["prog.tt", "--emit"]),
+        Err("--emit needs a value".to_string())
+    );
+    assert_eq!(
+        parse(&["--emit=x", "prog.tt"]),
+        Err("unknown --emit kind \"x\", expected tokens, ast or c".to_string())
+    );
+    assert_eq!(
+        parse(&["--frobnicate", "prog.tt"]),
+        Err("unknown option \"--frobnicate\"".to_string())
+    );
+    assert_eq!(
+        parse(&["-x=1", "prog.tt"]),
+        Err("unknown option \"-x=1\"".to_string())
+    );
+    assert_eq!(
+        parse(&["a.tt", "b.tt"]),
+        Err("unexpected argument \"b.tt\"".to_string())
+    );
+    assert_eq!(parse(&[]), Err("no input file given".to_string()));
+    assert_eq!(parse(&["-q"]), Err("no input file given".to_string()));
+}
+
+#[test]
+fn a_dash_reads_standard_input() {
+    assert_eq!(parse(&["-"]), Ok(Command::Compile(args("-"))));
+    assert_eq!(
+        parse(&["-o", "-", "-"]),
+        Ok(Command::Compile(Args {
+            output: Some("-".to_string()),
+            ..args("-")
+        }))
+    );
+}
+
+#[test]
+fn help_and_version_win_over_errors_after_them() {
+    assert_eq!(parse(&["-h", "--frobnicate"]), Ok(Command::Help));
+    assert_eq!(parse(&["run", "--version"]), Ok(Command::Version));
+}
+
+#[test]
+fn repl_takes_only_the_keyword_case_option() {
+    assert_eq!(
+        parse(&["repl"]),
+        Ok(Command::Repl {
+            ignore_keyword_case: false
+        })
+    );
+    assert_eq!(
+        parse(&["repl", "--ignore-keyword-case"]),
+        Ok(Command::Repl {
+            ignore_keyword_case: true
+        })
+    );
+    assert_eq!(
+        parse(&["repl", "prog.tt"]),
+        Err("unexpected argument \"prog.tt\"".to_string())
+    );
+    assert_eq!(
+        parse(&["repl", "-q"]),
+        Err("unexpected argument \"-q\"".to_string())
+    );
+    // `repl` is only a command in first place.
+    assert_eq!(
+        parse(&["prog.tt", "repl"]),
+        Err("unexpected argument \"repl\"".to_string())
+    );
+}
+
+#[test]
+fn compile_writes_c_to_out_c_and_inspection_to_the_terminal() {
+    assert_eq!(args("prog.tt").compile_output(), "out.c");
+    for emit in [Emit::Tokens, Emit::Ast] {
+        assert_eq!(
+            Args {
+                emit,
+                ..args("prog.tt")
+            }
+            .compile_output(),
+            "-"
+        );
+    }
+    let output = Some("prog.c".to_string());
+    assert_eq!(
+        Args {
+            output,
+            ..args("prog.tt")
+        }
+        .compile_output(),
+        "prog.c"
+    );
+}
+
+#[test]
+fn build_names_the_executable_after_the_source() {
+    assert_eq!(args("dir/prog.tt").build_output(), PathBuf::from("prog"));
+    // Without an extension the name would clash with the source.
+    assert_eq!(args("prog").build_output(), PathBuf::from("prog.out"));
+    assert_eq!(args("-").build_output(), PathBuf::from("a.out"));
+    let output = Some("bin/prog".to_string());
+    assert_eq!(
+        Args {
+            output,
+            ..args("prog.tt")
+        }
+        .build_output(),
+        PathBuf::from("bin/prog")
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn run_passes_on_the_program_exit_status() {
+    let status = |script: &str| {
+        std::process::Command::new("sh")
+            .args(["-c", script])
+            .status()
+            .unwrap()
+    };
+    assert_eq!(program_exit_status(status("exit 0")), EXIT_SUCCESS);
+    assert_eq!(program_exit_status(status("exit 7")), 7);
+    // A crash reads as 128 plus the signal, as in a shell, not as a compile error.
+    assert_eq!(program_exit_status(status("kill -SEGV $$")), 128 + 11);
+}
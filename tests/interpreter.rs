@@ -0,0 +1,444 @@
+use rust_basic_compiler::*;
+use std::io::Cursor;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn interpret(source: &str, input: &str) -> String {
+    let output = compile(source, &CompileOptions::default()).unwrap();
+    let mut printed = Vec::new();
+    Interpreter::new(Cursor::new(input), &mut printed)
+        .run(&output.program)
+        .unwrap();
+    String::from_utf8(printed).unwrap()
+}
+
+// Build the program with the system C compiler and run it, or None without one.
+fn run_c(source: &str, input: &str) -> Option<String> {
+    let output = compile(source, &CompileOptions::default()).unwrap();
+    let dir = scratch_dir().unwrap();
+    let exe = dir.join("program");
+    let toolchain = Toolchain::from_env();
+    if Command::new(&toolchain.cc)
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        return None;
+    }
+    toolchain.build(&output, "<source>", source, &exe).unwrap();
+    let printed = run_exe(&exe, input);
+    std::fs::remove_dir_all(&dir).unwrap();
+    Some(printed)
+}
+
+fn run_exe(exe: &Path, input: &str) -> String {
+    let mut child = Command::new(exe)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), input.as_bytes()).unwrap();
+    String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+}
+
+const AVERAGE: &str = include_str!("../test.txt");
+
+const ARITHMETIC: &str = "\
+LET a = 7
+PRINT 7 / 2
+PRINT 7 / 2.0
+PRINT a / 2
+PRINT -a * 3 + 1
+PRINT 1 / 3 * 3
+PRINT 10 / 4 * a
+LET x = 100000 * 100000
+PRINT x
+PRINT 2147483647 + 1
+";
+
+const STRINGS: &str = r#"PRINT "100% \"done\"\tok\\ ??= %d\n"
+PRINT "\\n is a newline\n"
+"#;
+
+const NUMBERS: &str = "\
+PRINT 0x1F + 0b1010_1010
+PRINT 1_000_000 / 3
+PRINT 010
+PRINT 1.5e-3 * 2e3
+PRINT 7 / 2e0
+PRINT 1.25E+2
+";
+
+const UNICODE: &str = "\
+LET größe = 3
+LET π = 314 / 100.0
+LET 变量 = größe * π
+PRINT \"größe × π = \"
+PRINT 变量
+GOTO ende
+PRINT \"skipped\"
+LABEL ende
+PRINT \"😀\\n\"
+";
+
+const C_NAMES: &str = "\
+LET int = 1
+LET main = 2
+LET printf = 3
+LET return = int + main + printf
+INPUT float
+LET a_b = 10
+LET a__b = 20
+LET _u00f6 = 30
+LET ö = 40
+LET _ = a_b + a__b + _u00f6 + ö
+GOTO while
+PRINT \"skipped\"
+LABEL while
+PRINT return
+PRINT float
+PRINT _
+";
+
+const PARENS: &str = "\
+LET a = 2
+LET b = 3
+LET c = 4
+PRINT (a + b) * c
+PRINT a - (b - c)
+PRINT a / (b * c)
+PRINT a - -b
+PRINT -(a + b) * -c
+PRINT 7 / (2 / 1.0)
+PRINT ((a))
+";
+
+const OPERATORS: &str = "\
+LET a = 7
+PRINT a % 3
+PRINT -7 MOD 3
+PRINT 7.5 % 2
+PRINT a \\ 2
+PRINT -a \\ 2
+PRINT 2 ^ 3 ^ 2
+PRINT -2 ^ 2
+PRINT 2 ^ -1
+PRINT (a + 1) ^ 2 / 4
+PRINT a - a % 4 * 2
+";
+
+const LOGIC: &str = "\
+LET a = 1
+LET b = 2
+IF a < b AND b < 3 THEN
+    PRINT \"and\"
+ENDIF
+IF a > b OR a == 1 AND b == 2 THEN
+    PRINT \"or\"
+ENDIF
+IF (a > b OR a == 1) AND b == 3 THEN
+    PRINT \"not printed\"
+ENDIF
+IF NOT a > b AND NOT NOT b == 2 THEN
+    PRINT \"not\"
+ENDIF
+IF (a + b) * 2 == 6 AND NOT (a == b) THEN
+    PRINT \"group\"
+ENDIF
+IF a == 1 OR 1 / 0 > 0 THEN
+    PRINT \"short\"
+ENDIF
+";
+
+const BRANCHES: &str = "\
+LET i = 0
+WHILE i < 4 REPEAT
+    IF i == 0 THEN
+        PRINT \"zero \"
+    ELSEIF i == 1 THEN
+        PRINT \"one \"
+    ELSEIF i == 2 THEN
+        IF 1 > 2 THEN
+            PRINT \"never\"
+        ELSE
+            PRINT \"two \"
+        ENDIF
+    ELSE
+        PRINT \"many \"
+    ENDIF
+    LET i = i + 1
+ENDWHILE
+IF i > 10 THEN
+ELSE
+    LABEL inelse
+    PRINT i
+ENDIF
+IF i < 5 THEN
+    LET i = 5
+    GOTO inelse
+ENDIF
+";
+
+const FOR: &str = "\
+FOR i = 1 TO 3
+    PRINT i
+NEXT
+FOR j = 10 TO 1 STEP -3
+    PRINT j
+NEXT j
+FOR k = 0 TO 1 STEP 0.25
+    FOR m = 1 TO k * 4 STEP 2
+        PRINT m
+    NEXT
+NEXT k
+PRINT i
+FOR n = 5 TO 1
+    PRINT \"never\"
+NEXT
+LET s = 0
+FOR q = 1 TO 10
+    LET s = s + q
+    IF q == 4 THEN
+        GOTO out
+    ENDIF
+    LABEL back
+NEXT
+LABEL out
+PRINT s
+IF q == 4 THEN
+    LET q = 8
+    GOTO back
+ENDIF
+PRINT s
+";
+
+const INPUTS: &str = "\
+INPUT a
+INPUT b
+INPUT c
+PRINT a
+PRINT b
+PRINT c
+";
+
+const GOTO: &str = "\
+LET i = 0
+GOTO inside
+WHILE i < 3 REPEAT
+    PRINT i
+    LABEL inside
+    LET i = i + 1
+    IF i == 5 THEN
+        GOTO done
+    ENDIF
+ENDWHILE
+PRINT \"not reached when i < 3 fails\"
+LABEL done
+PRINT i
+";
+
+#[test]
+fn interprets_the_average_program() {
+    assert_eq!(
+        interpret(AVERAGE, "3 1 2 4"),
+        "Enter number of scores: Enter one value at a time: Average: 2.33\n"
+    );
+}
+
+#[test]
+fn input_that_is_not_a_number_reads_as_zero() {
+    assert_eq!(
+        interpret(AVERAGE, "2 x 5"),
+        "Enter number of scores: Enter one value at a time: Average: 2.50\n"
+    );
+}
+
+#[test]
+fn input_reads_what_scanf_reads() {
+    assert_eq!(interpret(INPUTS, "inf 2 -NaN"), "inf\n2.00\n-nan\n");
+    assert_eq!(
+        interpret(INPUTS, "0x10 -0x1.8p1 0x.8"),
+        "16.00\n-3.00\n0.50\n"
+    );
+    // A partial `infinity` fails after reading the space, so the `%*s` skips the `2`.
+    assert_eq!(interpret(INPUTS, "infinit 2 3 4"), "0.00\n3.00\n4.00\n");
+    // The `p` of a decimal is left for the next INPUT, as is `x` after `0x` with no digits.
+    assert_eq!(interpret(INPUTS, "1p2 0xg 5"), "1.00\n0.00\n0.00\n");
+}
+
+#[test]
+fn arithmetic_follows_c_types() {
+    assert_eq!(
+        interpret(ARITHMETIC, ""),
+        "3.00\n3.50\n3.50\n-20.00\n0.00\n14.00\n1410065408.00\n-2147483648.00\n"
+    );
+}
+
+#[test]
+fn prints_strings_with_escapes() {
+    assert_eq!(
+        interpret(STRINGS, ""),
+        "100% \"done\"\tok\\ ??= %d\n\\n is a newline\n"
+    );
+}
+
+#[test]
+fn number_literals_keep_their_c_types() {
+    assert_eq!(
+        interpret(NUMBERS, ""),
+        "201.00\n333333.00\n10.00\n3.00\n3.50\n125.00\n"
+    );
+}
+
+#[test]
+fn unicode_names_and_strings() {
+    assert_eq!(interpret(UNICODE, ""), "größe × π = 9.42\n😀\n");
+    let c_code = compile(UNICODE, &CompileOptions::default()).unwrap().c_code;
+    assert!(c_code.contains("float tt_gr_u00f6_u00dfe;"));
+    assert!(c_code.contains("tt__u53d8_u91cf = tt_gr_u00f6_u00dfe*tt__u03c0;"));
+    assert!(c_code.contains("tt_ende:"));
+    assert!(c_code.contains("\"\\360\\237\\230\\200\\n\""));
+}
+
+#[test]
+fn names_cannot_clash_with_c() {
+    assert_eq!(interpret(C_NAMES, "7"), "6.00\n7.00\n100.00\n");
+    let c_code = compile(C_NAMES, &CompileOptions::default()).unwrap().c_code;
+    for declaration in [
+        "float tt_int;",
+        "float tt_main;",
+        "float tt_printf;",
+        "float tt_return;",
+        "float tt_float;",
+        "float tt_a__b;",
+        "float tt_a____b;",
+        "float tt___u00f6;",
+        "float tt__u00f6;",
+        "float tt___;",
+    ] {
+        assert!(c_code.contains(declaration), "{}", declaration);
+    }
+    assert!(c_code.contains("goto tt_while;"));
+    assert!(c_code.contains("tt_while:"));
+}
+
+#[test]
+fn parentheses_group_expressions() {
+    assert_eq!(
+        interpret(PARENS, ""),
+        "20.00\n3.00\n0.17\n5.00\n20.00\n3.50\n2.00\n"
+    );
+    let c_code = compile(PARENS, &CompileOptions::default()).unwrap().c_code;
+    assert!(c_code.contains("(float)((tt_a+tt_b)*tt_c)"));
+    assert!(c_code.contains("(float)(tt_a-(tt_b-tt_c))"));
+    // Not `a--b`, which C reads as a decrement.
+    assert!(c_code.contains("(float)(tt_a-(-tt_b))"));
+    assert!(c_code.contains("(float)(-(tt_a+tt_b)*(-tt_c))"));
+    assert!(c_code.contains("(float)(((tt_a)))"));
+}
+
+#[test]
+fn modulo_integer_division_and_powers() {
+    assert_eq!(
+        interpret(OPERATORS, ""),
+        "1.00\n-1.00\n1.50\n3.00\n-3.00\n512.00\n-4.00\n0.50\n16.00\n1.00\n"
+    );
+    let output = compile(OPERATORS, &CompileOptions::default()).unwrap();
+    assert!(output.needs_libm);
+    assert!(output
+        .c_code
+        .starts_with("#include <stdio.h>\n#include <math.h>\n"));
+    assert!(output.c_code.contains("(float)(trunc((double)-tt_a/2))"));
+    assert!(output.c_code.contains("(float)(pow(2,pow(3,2)))"));
+    assert!(output.c_code.contains("(float)(-pow(2,2))"));
+    assert!(output.c_code.contains("(float)(tt_a-fmod(tt_a,4)*2)"));
+    assert!(
+        !compile(PARENS, &CompileOptions::default())
+            .unwrap()
+            .needs_libm
+    );
+}
+
+#[test]
+fn conditions_combine_with_and_or_not() {
+    assert_eq!(interpret(LOGIC, ""), "andornotgroupshort");
+    let c_code = compile(LOGIC, &CompileOptions::default()).unwrap().c_code;
+    assert!(c_code.contains("if(tt_a<tt_b&&tt_b<3){"));
+    assert!(c_code.contains("if(tt_a>tt_b||tt_a==1&&tt_b==2){"));
+    assert!(c_code.contains("if((tt_a>tt_b||tt_a==1)&&tt_b==3){"));
+    assert!(c_code.contains("if(!(tt_a>tt_b)&&!!(tt_b==2)){"));
+    assert!(c_code.contains("if((tt_a+tt_b)*2==6&&!(tt_a==tt_b)){"));
+}
+
+#[test]
+fn if_takes_the_first_true_branch() {
+    assert_eq!(interpret(BRANCHES, ""), "zero one two many 4.00\n5.00\n");
+    let c_code = compile(BRANCHES, &CompileOptions::default())
+        .unwrap()
+        .c_code;
+    assert!(c_code.contains("}else if(tt_i==1){"));
+    assert!(c_code.contains("}else{"));
+}
+
+#[test]
+fn for_counts_up_and_down() {
+    assert_eq!(
+        interpret(FOR, ""),
+        "1.00\n2.00\n3.00\n10.00\n7.00\n4.00\n1.00\n1.00\n1.00\n1.00\n3.00\n1.00\n3.00\n\
+         4.00\n10.00\n29.00\n29.00\n"
+    );
+    let c_code = compile(FOR, &CompileOptions::default()).unwrap().c_code;
+    assert!(c_code.contains("float for1_end = 0;"));
+    assert!(c_code.contains("for(tt_i = 1, for1_end = 3; tt_i<=for1_end; tt_i += 1){"));
+    assert!(c_code.contains(
+        "for(tt_j = 10, for2_end = 1, for2_step = -3; \
+         for2_step<0 ? tt_j>=for2_end : tt_j<=for2_end; tt_j += for2_step){"
+    ));
+}
+
+#[test]
+fn goto_can_jump_into_a_loop_body() {
+    assert_eq!(
+        interpret(GOTO, ""),
+        "1.00\n2.00\nnot reached when i < 3 fails3.00\n"
+    );
+}
+
+#[test]
+fn integer_division_by_zero_is_a_runtime_error() {
+    let output = compile("PRINT 1 / 0\n", &CompileOptions::default()).unwrap();
+    let error = Interpreter::new(Cursor::new(""), Vec::new())
+        .run(&output.program)
+        .unwrap_err();
+    assert_eq!(error.code, "E0302");
+}
+
+#[test]
+fn interpreter_matches_the_c_backend() {
+    for (source, input) in [
+        (AVERAGE, "3 1 2 4"),
+        (AVERAGE, "2 x 5"),
+        (ARITHMETIC, ""),
+        (STRINGS, ""),
+        (NUMBERS, ""),
+        (UNICODE, ""),
+        (C_NAMES, "7"),
+        (PARENS, ""),
+        (OPERATORS, ""),
+        (LOGIC, ""),
+        (BRANCHES, ""),
+        (FOR, ""),
+        (GOTO, ""),
+        (INPUTS, "inf 2 -NaN"),
+        (INPUTS, "INFINITY nan(1) 3"),
+        (INPUTS, "0x10 -0x1.8p1 0x.8"),
+        (INPUTS, "0X1P-2 0x1p200 1e"),
+        (INPUTS, "infinit 2 3 4"),
+        (INPUTS, "infx nanx 0x"),
+        (INPUTS, "1p2 0xg 5"),
+    ] {
+        if let Some(expected) = run_c(source, input) {
+            assert_eq!(interpret(source, input), expected, "program:\n{}", source);
+        }
+    }
+}
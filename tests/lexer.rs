@@ -0,0 +1,578 @@
+use rust_basic_compiler::*;
+
+fn tokens(source: &str) -> Vec<(TokenType, String)> {
+    tokenize(source)
+        .unwrap()
+        .into_iter()
+        .map(|token| (token.kind, token.text))
+        .collect()
+}
+
+fn kinds(source: &str) -> Vec<TokenType> {
+    tokens(source).into_iter().map(|(kind, _)| kind).collect()
+}
+
+fn error_codes(source: &str) -> Vec<&'static str> {
+    tokenize(source)
+        .unwrap_err()
+        .into_iter()
+        .map(|diagnostic| diagnostic.code)
+        .collect()
+}
+
+fn token(kind: TokenType, text: &str) -> (TokenType, String) {
+    (kind, text.to_string())
+}
+
+#[test]
+fn lexes_every_keyword() {
+    use TokenType::*;
+    assert_eq!(
+        kinds("LABEL GOTO PRINT INPUT LET IF THEN ELSEIF ELSE ENDIF WHILE REPEAT ENDWHILE FOR TO STEP NEXT AND OR NOT MOD"),
+        vec![
+            LABEL, GOTO, PRINT, INPUT, LET, IF, THEN, ELSEIF, ELSE, ENDIF, WHILE, REPEAT, ENDWHILE, FOR, TO, STEP, NEXT, AND, OR, NOT,
+            MOD, EOF
+        ]
+    );
+}
+
+#[test]
+fn lexes_every_operator() {
+    use TokenType::*;
+    assert_eq!(
+        kinds("= + - * / % \\ ^ == != < <= > >= ( )"),
+        vec![
+            EQ, PLUS, MINUS, ASTERISK, SLASH, PERCENT, BACKSLASH, CARET, EQEQ, NOTEQ, LT, LTEQ, GT,
+            GTEQ, LPAREN, RPAREN, EOF
+        ]
+    );
+}
+
+#[test]
+fn lexes_literals_identifiers_and_newlines() {
+    use TokenType::*;
+    assert_eq!(
+        tokens("LET x1 = 42\nPRINT \"hi there\"\n"),
+        vec![
+            token(LET, "LET"),
+            token(IDENT, "x1"),
+            token(EQ, "="),
+            token(NUMBER, "42"),
+            token(NEWLINE, "\n"),
+            token(PRINT, "PRINT"),
+            token(STRING, "hi there"),
+            token(NEWLINE, "\n"),
+            token(EOF, "\0"),
+        ]
+    );
+}
+
+#[test]
+fn keywords_are_whole_words() {
+    use TokenType::*;
+    assert_eq!(
+        tokens("PRINTx LETS IFTHEN"),
+        vec![
+            token(IDENT, "PRINTx"),
+            token(IDENT, "LETS"),
+            token(IDENT, "IFTHEN"),
+            token(EOF, "\0"),
+        ]
+    );
+}
+
+#[test]
+fn lexes_tokens_at_the_end_of_the_source() {
+    use TokenType::*;
+    assert_eq!(tokens("x"), vec![token(IDENT, "x"), token(EOF, "\0")]);
+    assert_eq!(tokens("5"), vec![token(NUMBER, "5"), token(EOF, "\0")]);
+    assert_eq!(tokens("1.5"), vec![token(NUMBER, "1.5"), token(EOF, "\0")]);
+    assert_eq!(tokens("abc"), vec![token(IDENT, "abc"), token(EOF, "\0")]);
+    assert_eq!(tokens("<"), vec![token(LT, "<"), token(EOF, "\0")]);
+    assert_eq!(tokens(">="), vec![token(GTEQ, ">="), token(EOF, "\0")]);
+    assert_eq!(tokens("\"s\""), vec![token(STRING, "s"), token(EOF, "\0")]);
+    assert_eq!(error_codes("!"), vec!["E0001"]);
+}
+
+#[test]
+fn empty_and_blank_sources_have_a_single_eof() {
+    use TokenType::*;
+    assert_eq!(kinds(""), vec![EOF]);
+    assert_eq!(kinds("  \t "), vec![EOF]);
+    assert_eq!(kinds("\n\n"), vec![NEWLINE, NEWLINE, EOF]);
+
+    let mut lexer = Lexer::new("x".to_string());
+    assert_eq!(lexer.next().unwrap().unwrap().kind, IDENT);
+    assert_eq!(lexer.next().unwrap().unwrap().kind, EOF);
+    assert!(lexer.next().is_none());
+    assert!(lexer.next().is_none());
+}
+
+#[test]
+fn comment_at_the_end_of_the_source() {
+    use TokenType::*;
+    assert_eq!(kinds("PRINT 1\n# done"), vec![PRINT, NUMBER, NEWLINE, EOF]);
+    assert_eq!(kinds("PRINT 1 #[ done ]#"), vec![PRINT, NUMBER, EOF]);
+}
+
+#[test]
+fn line_comments_keep_the_newline() {
+    use TokenType::*;
+    assert_eq!(
+        kinds("# heading\nLET a = 1 # init\n#\nPRINT a#no space\n"),
+        vec![NEWLINE, LET, IDENT, EQ, NUMBER, NEWLINE, NEWLINE, PRINT, IDENT, NEWLINE, EOF]
+    );
+}
+
+#[test]
+fn block_comments_span_lines_and_nest() {
+    use TokenType::*;
+    assert_eq!(
+        tokens("LET #[ a\n #[ nested\n ]# b ]# a = 1 #[x]##[y]# # tail\n"),
+        vec![
+            token(LET, "LET"),
+            token(IDENT, "a"),
+            token(EQ, "="),
+            token(NUMBER, "1"),
+            token(NEWLINE, "\n"),
+            token(EOF, "\0"),
+        ]
+    );
+    assert_eq!(kinds("#[]#PRINT"), vec![PRINT, EOF]);
+    assert_eq!(kinds("#[ # ]# x"), vec![IDENT, EOF]);
+
+    let diagnostics = tokenize("PRINT 1\n#[ #[ ]#\n").unwrap_err();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "E0006");
+    assert_eq!(diagnostics[0].span.unwrap().line, 2);
+}
+
+#[test]
+fn lexes_decimals() {
+    use TokenType::*;
+    assert_eq!(
+        tokens("1.55 0.5 10.25\n"),
+        vec![
+            token(NUMBER, "1.55"),
+            token(NUMBER, "0.5"),
+            token(NUMBER, "10.25"),
+            token(NEWLINE, "\n"),
+            token(EOF, "\0"),
+        ]
+    );
+    assert_eq!(error_codes("1."), vec!["E0003"]);
+    assert_eq!(error_codes("1.x"), vec!["E0003"]);
+    assert_eq!(error_codes("1. "), vec!["E0003"]);
+}
+
+#[test]
+fn numbers_keep_the_token_after_them() {
+    use TokenType::*;
+    assert_eq!(
+        tokens("5\n6+7*8"),
+        vec![
+            token(NUMBER, "5"),
+            token(NEWLINE, "\n"),
+            token(NUMBER, "6"),
+            token(PLUS, "+"),
+            token(NUMBER, "7"),
+            token(ASTERISK, "*"),
+            token(NUMBER, "8"),
+            token(EOF, "\0"),
+        ]
+    );
+    assert_eq!(
+        tokens("2.5/x"),
+        vec![
+            token(NUMBER, "2.5"),
+            token(SLASH, "/"),
+            token(IDENT, "x"),
+            token(EOF, "\0"),
+        ]
+    );
+}
+
+#[test]
+fn lexes_adjacent_operators() {
+    use TokenType::*;
+    assert_eq!(kinds("<=="), vec![LTEQ, EQ, EOF]);
+    assert_eq!(kinds("==="), vec![EQEQ, EQ, EOF]);
+    assert_eq!(kinds("=<"), vec![EQ, LT, EOF]);
+    assert_eq!(kinds("<>"), vec![LT, GT, EOF]);
+    assert_eq!(kinds(">=="), vec![GTEQ, EQ, EOF]);
+    assert_eq!(kinds("!==="), vec![NOTEQ, EQEQ, EOF]);
+    assert_eq!(kinds("+-*/"), vec![PLUS, MINUS, ASTERISK, SLASH, EOF]);
+    assert_eq!(kinds("a<b"), vec![IDENT, LT, IDENT, EOF]);
+    assert_eq!(kinds("x=-1"), vec![IDENT, EQ, MINUS, NUMBER, EOF]);
+    assert_eq!(
+        kinds("((a)*-(1))"),
+        vec![LPAREN, LPAREN, IDENT, RPAREN, ASTERISK, MINUS, LPAREN, NUMBER, RPAREN, RPAREN, EOF]
+    );
+}
+
+#[test]
+fn spans_cover_each_token() {
+    let tokens = tokenize("LET ab = 1.25\n  PRINT ab >= 3").unwrap();
+    let spans: Vec<(&str, usize, usize, usize, usize)> = tokens
+        .iter()
+        .map(|t| {
+            (
+                t.text.as_str(),
+                t.span.start,
+                t.span.end,
+                t.span.line,
+                t.span.column,
+            )
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        vec![
+            ("LET", 0, 3, 1, 1),
+            ("ab", 4, 6, 1, 5),
+            ("=", 7, 8, 1, 8),
+            ("1.25", 9, 13, 1, 10),
+            ("\n", 13, 14, 1, 14),
+            ("PRINT", 16, 21, 2, 3),
+            ("ab", 22, 24, 2, 9),
+            (">=", 25, 27, 2, 12),
+            ("3", 28, 29, 2, 15),
+            ("\0", 29, 29, 2, 16),
+        ]
+    );
+}
+
+#[test]
+fn reports_bad_characters_and_carries_on() {
+    let diagnostics = tokenize("LET a = 1 ! 2 @\nPRINT \"a\\qb\"\nPRINT \"open").unwrap_err();
+    let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+    assert_eq!(codes, vec!["E0001", "E0004", "E0002", "E0005"]);
+    assert_eq!(diagnostics[1].span.unwrap().column, 15);
+}
+
+#[test]
+fn a_nul_character_is_not_the_end_of_input() {
+    let diagnostics = tokenize("PRINT 1\n\0PRINT 2\n").unwrap_err();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "E0004");
+    assert_eq!(diagnostics[0].message, "Unknown token: '\\0'");
+    assert_eq!(
+        diagnostics[0].span,
+        Some(Span {
+            start: 8,
+            end: 9,
+            line: 2,
+            column: 1,
+        })
+    );
+    assert_eq!(error_codes("a\0b"), vec!["E0004"]);
+    assert!(tokenize_with_trivia("a\0b").is_err());
+    // Inside strings and comments it is just another character.
+    assert_eq!(
+        tokens("PRINT \"a\0b\" # c\0d\n#[ \0 ]#"),
+        vec![
+            token(TokenType::PRINT, "PRINT"),
+            token(TokenType::STRING, "a\0b"),
+            token(TokenType::NEWLINE, "\n"),
+            token(TokenType::EOF, "\0"),
+        ]
+    );
+}
+
+#[test]
+fn decodes_string_escapes() {
+    let tokens = tokenize(r#""tab\there \"quoted\" back\\slash\n 100%""#).unwrap();
+    assert_eq!(tokens[0].kind, TokenType::STRING);
+    assert_eq!(tokens[0].text, r#"tab\there \"quoted\" back\\slash\n 100%"#);
+    assert_eq!(
+        tokens[0].value.as_deref(),
+        Some("tab\there \"quoted\" back\\slash\n 100%")
+    );
+    assert_eq!(tokens[1].kind, TokenType::EOF);
+
+    assert_eq!(error_codes(r#""bad \q escape""#), vec!["E0002"]);
+    assert_eq!(error_codes("\"ends with \\\n"), vec!["E0005"]);
+    assert_eq!(error_codes(r#""\""#), vec!["E0005"]);
+}
+
+#[test]
+fn trivia_reproduces_the_source() {
+    for source in [
+        include_str!("../test.txt"),
+        "",
+        "   ",
+        "# only a comment",
+        "LET a = 1.5   # init\r\n\tPRINT \"say \\\"hi\\\"\"\n#[ outer #[ inner ]#\n ]#PRINT a>=2 ",
+        "\n\nWHILE a<3 REPEAT\n  LET a=a+1\nENDWHILE #[ end ]#",
+    ] {
+        let tokens = tokenize_with_trivia(source).unwrap();
+        let rebuilt: String = tokens.iter().map(Token::source).collect();
+        assert_eq!(rebuilt, source);
+    }
+}
+
+#[test]
+fn trivia_is_attached_to_the_following_token() {
+    let tokens = tokenize_with_trivia("LET  a #[ c ]# = 1 # note\n").unwrap();
+    let leading: Vec<Vec<(TriviaKind, &str)>> = tokens
+        .iter()
+        .map(|t| {
+            t.leading
+                .iter()
+                .map(|l| (l.kind, l.text.as_str()))
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        leading,
+        vec![
+            vec![],
+            vec![(TriviaKind::Whitespace, "  ")],
+            vec![
+                (TriviaKind::Whitespace, " "),
+                (TriviaKind::BlockComment, "#[ c ]#"),
+                (TriviaKind::Whitespace, " "),
+            ],
+            vec![(TriviaKind::Whitespace, " ")],
+            vec![
+                (TriviaKind::Whitespace, " "),
+                (TriviaKind::LineComment, "# note"),
+            ],
+            vec![],
+        ]
+    );
+    assert_eq!(tokens[2].leading[1].span.start, 7);
+    assert_eq!(tokens[2].leading[1].span.end, 14);
+
+    // Without trivia mode the same tokens carry none.
+    assert!(tokenize("LET  a # note\n")
+        .unwrap()
+        .iter()
+        .all(|t| t.leading.is_empty()));
+}
+
+fn number(source: &str) -> (String, Option<String>) {
+    let token = tokenize(source).unwrap().remove(0);
+    assert_eq!(token.kind, TokenType::NUMBER, "{:?}", source);
+    (token.text, token.value)
+}
+
+#[test]
+fn number_values_are_spelled_for_c() {
+    for (source, value) in [
+        ("42", "42"),
+        ("007", "7"),
+        ("0x1F", "31"),
+        ("0XfF", "255"),
+        ("0b1010", "10"),
+        ("0B1_0000", "16"),
+        ("1_000_000", "1000000"),
+        ("0x7FFF_FFFF", "2147483647"),
+        ("1.5", "1.5"),
+        ("1_000.000_1", "1000.0001"),
+        ("1.5e-3", "1.5e-3"),
+        ("2E3", "2e3"),
+        ("6.02e+23", "6.02e+23"),
+        ("1e1_0", "1e10"),
+    ] {
+        assert_eq!(
+            number(source),
+            (source.to_string(), Some(value.to_string()))
+        );
+    }
+    assert_eq!(
+        tokens("0x1F+0b1"),
+        vec![
+            token(TokenType::NUMBER, "0x1F"),
+            token(TokenType::PLUS, "+"),
+            token(TokenType::NUMBER, "0b1"),
+            token(TokenType::EOF, "\0"),
+        ]
+    );
+}
+
+#[test]
+fn malformed_numbers_are_one_error() {
+    for source in [
+        "0x", "0b", "0b102", "0x1G", "1__0", "1_", "0x_1", "1_.5", "1._5", "1.5_", "1e", "1e+",
+        "1ex", "1.",
+    ] {
+        assert_eq!(error_codes(source), vec!["E0003"], "{:?}", source);
+    }
+    // `_` cannot start a number, so this is an identifier.
+    assert_eq!(kinds("_1"), vec![TokenType::IDENT, TokenType::EOF]);
+}
+
+#[test]
+fn number_overflow_and_precision() {
+    assert_eq!(number("2147483647").1.as_deref(), Some("2147483647"));
+    // Too large for an `int`, so spelled as a double like C's `long` would convert.
+    assert_eq!(number("2147483648").1.as_deref(), Some("2147483648.0"));
+    assert_eq!(number("3_000_000_000").1.as_deref(), Some("3000000000.0"));
+    assert_eq!(number("0x1_0000_0000").1.as_deref(), Some("4.294967296e9"));
+    assert_eq!(error_codes("1e999"), vec!["E0007"]);
+    assert_eq!(error_codes(&format!("1{}", "0".repeat(400))), vec!["E0007"]);
+    assert_eq!(
+        error_codes(&format!("0x1{}", "0".repeat(300))),
+        vec!["E0007"]
+    );
+
+    let warnings = |source: &str| {
+        let output = compile(&format!("PRINT {}\n", source), &CompileOptions::default()).unwrap();
+        output
+            .warnings
+            .iter()
+            .map(|d| (d.severity, d.code))
+            .collect::<Vec<_>>()
+    };
+    for source in [
+        "16777217",
+        "2147483647",
+        "0x7FFF_FFFF",
+        "1e300",
+        "1e-400",
+        "0.123456789012345678",
+        "3000000001",
+        "0xFFFF_FFFF",
+        "99999999999999999999999",
+    ] {
+        assert_eq!(
+            warnings(source),
+            vec![(Severity::Warning, "W0001")],
+            "{:?}",
+            source
+        );
+    }
+    for source in ["16777216", "1e30", "0.1", "0.000", "0e5", "3000000000"] {
+        assert_eq!(warnings(source), vec![], "{:?}", source);
+    }
+}
+
+fn kinds_with(source: &str, keywords: Keywords) -> Vec<TokenType> {
+    let mut lexer = Lexer::new(source.to_string());
+    lexer.keywords = keywords;
+    lex(lexer).unwrap().into_iter().map(|t| t.kind).collect()
+}
+
+#[test]
+fn keywords_are_case_sensitive_by_default() {
+    use TokenType::*;
+    assert_eq!(kinds("print Print PRINT"), vec![IDENT, IDENT, PRINT, EOF]);
+    assert_eq!(Keywords::new().lookup("GOTO"), Some(GOTO));
+    assert_eq!(Keywords::new().lookup("goto"), None);
+}
+
+#[test]
+fn keywords_can_ignore_case() {
+    use TokenType::*;
+    let mut keywords = Keywords::new();
+    keywords.case_insensitive = true;
+    assert_eq!(
+        kinds_with("print Let wHiLe x endWhile", keywords),
+        vec![PRINT, LET, WHILE, IDENT, ENDWHILE, EOF]
+    );
+
+    let options = CompileOptions {
+        keywords: Keywords {
+            case_insensitive: true,
+            ..Keywords::new()
+        },
+        ..CompileOptions::default()
+    };
+    let output = compile("let a = 1\nprint a\n", &options).unwrap();
+    assert!(output.c_code.contains("a = 1;"));
+    assert!(compile("let a = 1\n", &CompileOptions::default()).is_err());
+
+    // Only ASCII letters fold: a long s or dotless i does not make a keyword.
+    assert_eq!(options.keywords.lookup("ſtep"), None);
+    assert_eq!(options.keywords.lookup("prınt"), None);
+    assert_eq!(options.keywords.lookup("sTeP"), Some(STEP));
+    let output = compile(
+        "LET ſtep = 1
+PRINT ſtep
+",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(output.program.statements.len(), 2);
+}
+
+#[test]
+fn dialects_can_add_keywords() {
+    use TokenType::*;
+    let mut keywords = Keywords::new();
+    keywords.insert("wend", ENDWHILE);
+    keywords.insert("DO", REPEAT);
+    assert_eq!(
+        kinds_with("WHILE x DO WEND wend", keywords.clone()),
+        vec![WHILE, IDENT, REPEAT, ENDWHILE, IDENT, EOF]
+    );
+    keywords.case_insensitive = true;
+    assert_eq!(
+        kinds_with("wend Wend", keywords),
+        vec![ENDWHILE, ENDWHILE, EOF]
+    );
+}
+
+#[test]
+fn lexes_unicode_identifiers_and_strings() {
+    use TokenType::*;
+    let tokens = tokenize("LET größe = 1\nPRINT \"π ≈ 3.14 😀\"\nPRINT 变量").unwrap();
+    let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![LET, IDENT, EQ, NUMBER, NEWLINE, PRINT, STRING, NEWLINE, PRINT, IDENT, EOF]
+    );
+    assert_eq!(tokens[1].text, "größe");
+    assert_eq!((tokens[1].span.start, tokens[1].span.end), (4, 11));
+    assert_eq!(tokens[2].span.column, 11);
+    assert_eq!(tokens[6].value.as_deref(), Some("π ≈ 3.14 😀"));
+    assert_eq!(tokens[9].text, "变量");
+    assert_eq!((tokens[10].span.line, tokens[10].span.column), (3, 9));
+
+    let diagnostics = tokenize("LET a = 1 € 2").unwrap_err();
+    assert_eq!(diagnostics[0].code, "E0004");
+    let span = diagnostics[0].span.unwrap();
+    assert_eq!((span.start, span.end, span.column), (10, 13, 11));
+}
+
+#[test]
+fn identifiers_follow_xid_classes() {
+    use TokenType::*;
+    // `e` followed by a combining acute accent (U+0301), which can continue a name.
+    assert_eq!(
+        tokens("LET cafe\u{301} = 1"),
+        vec![
+            token(LET, "LET"),
+            token(IDENT, "cafe\u{301}"),
+            token(EQ, "="),
+            token(NUMBER, "1"),
+            token(EOF, "\0"),
+        ]
+    );
+    // A combining mark cannot start a name.
+    assert_eq!(error_codes("\u{301}a"), vec!["E0004"]);
+    // Superscripts and fractions are numeric symbols, not name characters; letter numbers
+    // such as the Roman numeral eight are letters.
+    assert_eq!(error_codes("LET x\u{b2} = 1"), vec!["E0004"]);
+    assert_eq!(error_codes("LET \u{bd} = 1"), vec!["E0004"]);
+    assert_eq!(kinds("\u{2167}"), vec![IDENT, EOF]);
+}
+
+#[test]
+fn identifiers_may_contain_underscores() {
+    use TokenType::*;
+    assert_eq!(
+        tokens("LET _a_b_ = __ + x_1"),
+        vec![
+            token(LET, "LET"),
+            token(IDENT, "_a_b_"),
+            token(EQ, "="),
+            token(IDENT, "__"),
+            token(PLUS, "+"),
+            token(IDENT, "x_1"),
+            token(EOF, "\0"),
+        ]
+    );
+    assert_eq!(kinds("PRINT_"), vec![IDENT, EOF]);
+}
@@ -0,0 +1,81 @@
+use rust_basic_compiler::*;
+
+fn error_codes(source: &str) -> Vec<&'static str> {
+    compile(source, &CompileOptions::default())
+        .err()
+        .unwrap_or_default()
+        .iter()
+        .map(|diagnostic| diagnostic.code)
+        .collect()
+}
+
+#[test]
+fn reports_every_broken_statement() {
+    let source = "PRINT +\nLET = 1\nGOTO\nPRINT 1\nFOO\n";
+    assert_eq!(
+        error_codes(source),
+        vec!["E0104", "E0101", "E0101", "E0102"]
+    );
+}
+
+#[test]
+fn resynchronizes_on_block_terminators() {
+    // The broken header and statements are reported, and ENDIF/ENDWHILE still close
+    // their blocks, so nothing after them is misreported.
+    let source = "\
+IF 1 THEN
+    PRINT +
+ENDIF
+WHILE 1 < 2 REPEAT
+    LET = 2
+ENDWHILE
+PRINT 3
+";
+    assert_eq!(error_codes(source), vec!["E0103", "E0104", "E0101"]);
+
+    // A broken statement right before the terminator does not swallow it.
+    let source = "WHILE 1 < 2 REPEAT\nIF 1 < 2 THEN\nPRINT *\nENDIF\nENDWHILE\n";
+    assert_eq!(error_codes(source), vec!["E0104"]);
+}
+
+#[test]
+fn a_lexer_error_is_not_reported_again_by_the_parser() {
+    assert_eq!(error_codes("LET w = 0x\nPRINT 1\n"), vec!["E0003"]);
+    assert_eq!(error_codes("LET v = 1__0\nPRINT 1\n"), vec!["E0003"]);
+    assert_eq!(error_codes("LET u = 1.\nPRINT 1\n"), vec!["E0003"]);
+    assert_eq!(error_codes("PRINT @\n"), vec!["E0004"]);
+    // A later, unrelated error in the same statement is still reported.
+    assert_eq!(error_codes("LET = 1 @\n"), vec!["E0101", "E0004"]);
+}
+
+#[test]
+fn an_unterminated_string_ends_at_the_end_of_its_line() {
+    assert_eq!(error_codes("PRINT \"abc\nPRINT 1\n"), vec!["E0005"]);
+    assert_eq!(
+        error_codes("PRINT \"abc\nPRINT +\n"),
+        vec!["E0005", "E0104"]
+    );
+    assert_eq!(
+        error_codes("IF 1 < 2 THEN\nPRINT \"abc\nENDIF\n"),
+        vec!["E0005"]
+    );
+}
+
+#[test]
+fn comparisons_do_not_chain() {
+    assert_eq!(error_codes("IF 1 < 2 < 3 THEN\nENDIF\n"), vec!["E0109"]);
+    assert_eq!(error_codes("IF 1 AND 2 < 3 THEN\nENDIF\n"), vec!["E0103"]);
+}
+
+#[test]
+fn else_ends_the_branches() {
+    let source = "IF 1 < 2 THEN\nELSE\nELSEIF 1 < 2 THEN\nENDIF\n";
+    assert_eq!(error_codes(source), vec!["E0102"]);
+    assert_eq!(error_codes("IF 1 < 2 THEN\nELSE\n"), vec!["E0108"]);
+}
+
+#[test]
+fn next_names_the_loop_variable() {
+    assert_eq!(error_codes("FOR i = 1 TO 2\nNEXT j\n"), vec!["E0110"]);
+    assert_eq!(error_codes("FOR i = 1 TO 2\n"), vec!["E0108"]);
+}
@@ -0,0 +1,135 @@
+use rust_basic_compiler::*;
+
+fn span(start: usize, end: usize, line: usize, column: usize) -> Option<Span> {
+    Some(Span {
+        start,
+        end,
+        line,
+        column,
+    })
+}
+
+fn render(diagnostic: &Diagnostic, source: &str) -> String {
+    Renderer::plain().render(diagnostic, "test.tt", source)
+}
+
+#[test]
+fn underlines_the_span_below_its_line() {
+    let source = "LET a = 1\nPRINT bb + 1\n";
+    let diagnostic = Diagnostic::error("E0105", "Unknown b".to_string(), span(16, 18, 2, 7));
+    assert_eq!(
+        render(&diagnostic, source),
+        "\
+error[E0105]: Unknown b
+ --> test.tt:2:7
+  |
+2 | PRINT bb + 1
+  |       ^^
+"
+    );
+}
+
+#[test]
+fn keeps_tabs_in_the_indent() {
+    let source = "IF a < 1 THEN\n\t\tPRINT b\nENDIF\n";
+    let diagnostic = Diagnostic::error("E0105", "Unknown b".to_string(), span(22, 23, 2, 9));
+    assert_eq!(
+        render(&diagnostic, source),
+        "\
+error[E0105]: Unknown b
+ --> test.tt:2:9
+  |
+2 | \t\tPRINT b
+  | \t\t      ^
+"
+    );
+}
+
+#[test]
+fn counts_characters_not_bytes() {
+    // `größe` is 7 bytes but 5 characters, and `€` is 3 bytes.
+    let source = "LET größe = 1 € 2\n";
+    let diagnostic = Diagnostic::warning("W9999", "Name".to_string(), span(4, 11, 1, 5));
+    assert_eq!(
+        render(&diagnostic, source),
+        "\
+warning[W9999]: Name
+ --> test.tt:1:5
+  |
+1 | LET größe = 1 € 2
+  |     ^^^^^
+"
+    );
+    let diagnostic = Diagnostic::error("E0004", "Unknown".to_string(), span(16, 19, 1, 15));
+    assert!(render(&diagnostic, source).ends_with("1 | LET größe = 1 € 2\n  |               ^\n"));
+}
+
+#[test]
+fn marks_one_column_at_the_end_of_a_line_and_of_the_source() {
+    // A span on the newline itself.
+    let diagnostic = Diagnostic::error("E0104", "Unexpected".to_string(), span(7, 8, 1, 8));
+    assert!(render(&diagnostic, "PRINT +\r\nPRINT 1\n").ends_with("1 | PRINT +\n  |        ^\n"));
+    // An empty span at the end of a source without a final newline.
+    let diagnostic = Diagnostic::error("E0104", "Unexpected".to_string(), span(5, 5, 1, 6));
+    assert!(render(&diagnostic, "PRINT").ends_with("1 | PRINT\n  |      ^\n"));
+    // At the end of a source that ends with a newline, the last line is empty.
+    let diagnostic = Diagnostic::error("E0108", "Unclosed".to_string(), span(8, 8, 2, 1));
+    assert!(render(&diagnostic, "PRINT 1\n").ends_with(" --> test.tt:2:1\n  |\n2 | \n  | ^\n"));
+    // A span running past its line is cut at the end of the line.
+    let diagnostic = Diagnostic::error("E0006", "Unclosed".to_string(), span(0, 9, 1, 1));
+    assert!(render(&diagnostic, "#[ a\nb ]#").ends_with("1 | #[ a\n  | ^^^^\n"));
+}
+
+#[test]
+fn notes_share_the_gutter_width() {
+    let source = "LABEL a\n\n\n\n\n\n\n\n\nLABEL a\n";
+    let diagnostic = Diagnostic::error("E0106", "Label exists".to_string(), span(22, 23, 10, 7))
+        .with_note("first declared here".to_string(), span(6, 7, 1, 7))
+        .with_note("labels are global".to_string(), None);
+    assert_eq!(
+        render(&diagnostic, source),
+        "\
+error[E0106]: Label exists
+  --> test.tt:10:7
+   |
+10 | LABEL a
+   |       ^
+note: first declared here
+  --> test.tt:1:7
+   |
+ 1 | LABEL a
+   |       -
+note: labels are global
+"
+    );
+}
+
+#[test]
+fn renders_without_a_span() {
+    let diagnostic = Diagnostic::error("E0201", "No compiler".to_string(), None);
+    assert_eq!(render(&diagnostic, ""), "error[E0201]: No compiler\n");
+}
+
+#[test]
+fn colors_only_when_asked() {
+    let diagnostic = Diagnostic::error("E0104", "Bad".to_string(), span(6, 7, 1, 7))
+        .with_note("here".to_string(), None);
+    let plain = render(&diagnostic, "PRINT +\n");
+    assert!(!plain.contains('\x1b'));
+    assert_eq!(
+        Renderer::colored().render(&diagnostic, "test.tt", "PRINT +\n"),
+        "\
+\x1b[1;31merror[E0104]\x1b[0m\x1b[1m: Bad\x1b[0m
+ \x1b[1;34m-->\x1b[0m test.tt:1:7
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m PRINT +
+  \x1b[1;34m|\x1b[0m       \x1b[1;31m^\x1b[0m
+\x1b[1;32mnote\x1b[0m: here
+"
+    );
+    let warning = Diagnostic::warning("W0001", "Big".to_string(), None);
+    assert_eq!(
+        Renderer::colored().render(&warning, "test.tt", ""),
+        "\x1b[1;33mwarning[W0001]\x1b[0m\x1b[1m: Big\x1b[0m\n"
+    );
+}
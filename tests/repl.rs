@@ -0,0 +1,49 @@
+use rust_basic_compiler::*;
+use std::io::Cursor;
+
+fn session(input: &str) -> String {
+    let mut output = Vec::new();
+    Repl::new(Cursor::new(input), &mut output).run().unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn variables_persist_between_entries() {
+    assert_eq!(session("LET a = 2\nLET a = a * 3\nPRINT a\n"), "> > > 6.00\n> \n");
+}
+
+#[test]
+fn unterminated_blocks_continue_on_the_next_line() {
+    assert_eq!(
+        session("LET i = 0\nWHILE i < 2 REPEAT\nPRINT i\nLET i = i + 1\nENDWHILE\n"),
+        "> > .. .. .. 0.00\n1.00\n> \n"
+    );
+}
+
+#[test]
+fn errors_are_reported_and_the_session_continues() {
+    let output = session("PRINT b\nLET b = 1\nPRINT b\n");
+    assert!(output.contains("error[E0105]"));
+    assert!(output.ends_with("> > 1.00\n> \n"));
+}
+
+#[test]
+fn meta_commands_show_tokens_and_c() {
+    let output = session(":tokens LET x = 1\n:c PRINT \"hi\"\n:quit\n");
+    assert!(output.contains("LET \"LET\"\nIDENT \"x\"\nEQ \"=\"\nNUMBER \"1\""));
+    assert!(output.contains("printf(\"%s\", \"hi\");"));
+}
+
+#[test]
+fn entries_use_the_repl_keywords() {
+    let mut output = Vec::new();
+    let mut repl = Repl::new(
+        Cursor::new(":tokens print 1\nlet a = 2\nprint a\n"),
+        &mut output,
+    );
+    repl.keywords.case_insensitive = true;
+    repl.run().unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("PRINT \"print\"\nNUMBER \"1\""));
+    assert!(output.ends_with("> > 2.00\n> \n"));
+}
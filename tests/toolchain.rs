@@ -0,0 +1,201 @@
+use rust_basic_compiler::*;
+use std::fs;
+use std::process::Command;
+
+const SOURCE: &str = "LET a = 1\n    PRINT a  \nLET b = a\n";
+
+fn diagnostics(stderr: &str) -> Vec<Diagnostic> {
+    c_diagnostics(stderr, "exit status: 1", "h.tt", SOURCE)
+}
+
+fn lines(diagnostics: &[Diagnostic]) -> Vec<Option<usize>> {
+    diagnostics
+        .iter()
+        .map(|d| d.span.map(|span| span.line))
+        .collect()
+}
+
+#[test]
+fn maps_c_errors_to_teeny_tiny_lines() {
+    // gcc, with the context it prints around the error.
+    let stderr = "\
+h.tt: In function 'main':
+h.tt:2:6: error: 'tt_c' undeclared (first use in this function)
+    2 |     PRINT a
+      |      ^~~~
+h.tt:2:6: note: each undeclared identifier is reported only once for each function it appears in
+";
+    let diagnostics = diagnostics(stderr);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "E0202");
+    assert_eq!(
+        diagnostics[0].message,
+        "C compiler: 'tt_c' undeclared (first use in this function)"
+    );
+    // The whole line without its indentation or trailing spaces.
+    assert_eq!(
+        diagnostics[0].span,
+        Some(Span {
+            start: 14,
+            end: 21,
+            line: 2,
+            column: 5,
+        })
+    );
+}
+
+#[test]
+fn reads_clang_and_tcc_locations() {
+    // clang gives a column, tcc only the line.
+    let clang = "\
+h.tt:3:6: error: expression is not assignable
+    3 | LET b = a
+      | ~~~~ ^
+1 error generated.
+";
+    assert_eq!(lines(&diagnostics(clang)), vec![Some(3)]);
+    assert_eq!(
+        lines(&diagnostics("h.tt:1: error: lvalue expected\n")),
+        vec![Some(1)]
+    );
+}
+
+#[test]
+fn maps_fatal_errors() {
+    let diagnostics = diagnostics(
+        "h.tt:1:10: fatal error: missing.h: No such file or directory\ncompilation terminated.\n",
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "C compiler: missing.h: No such file or directory"
+    );
+    assert_eq!(lines(&diagnostics), vec![Some(1)]);
+}
+
+#[test]
+fn an_error_past_the_end_of_the_source_has_no_span() {
+    let diagnostics = diagnostics("h.tt:40:1: error: expected declaration\n");
+    assert_eq!(lines(&diagnostics), vec![None]);
+}
+
+#[test]
+fn errors_in_other_files_are_not_mapped() {
+    // An error in a header given with `-include`: it keeps its own location, even though
+    // line 3 of the source exists.
+    let stderr = "\
+In file included from <command-line>:
+/tmp/bad.h:3:9: error: expected expression before ';' token
+    3 | int y = ;
+      |         ^
+h.tt: In function 'main':
+<command-line>: error: expected identifier or '(' before numeric constant
+h.tt:3:6: error: lvalue required as left operand of assignment
+";
+    let mixed = diagnostics(stderr);
+    assert_eq!(lines(&mixed), vec![Some(3), None]);
+    assert_eq!(
+        mixed[1].message,
+        format!(
+            "The C compiler failed (exit status: 1):\n{}",
+            stderr.trim_end()
+        )
+    );
+    // A file whose name starts with the source's is another file too.
+    let stderr = "h.tt.h:1:1: error: unknown type name 'x'\n";
+    assert_eq!(lines(&diagnostics(stderr)), vec![None]);
+    // So is the command line, where gcc reports a missing `-include` header.
+    let stderr = "\
+<command-line>: fatal error: missing.h: No such file or directory
+compilation terminated.
+";
+    assert_eq!(lines(&diagnostics(stderr)), vec![None]);
+}
+
+#[test]
+fn other_failures_report_the_whole_output() {
+    let stderr = "\
+/usr/bin/ld: /usr/lib/x86_64-linux-gnu/Scrt1.o: in function `_start':
+(.text+0x17): undefined reference to `main'
+collect2: error: ld returned 1 exit status
+";
+    let failures = diagnostics(stderr);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].code, "E0202");
+    assert_eq!(failures[0].span, None);
+    assert_eq!(
+        failures[0].message,
+        format!(
+            "The C compiler failed (exit status: 1):\n{}",
+            stderr.trim_end()
+        )
+    );
+
+    let empty = diagnostics("");
+    assert_eq!(
+        empty[0].message,
+        "The C compiler failed (exit status: 1):\n"
+    );
+}
+
+#[test]
+fn names_the_source_in_line_directives() {
+    let options = CompileOptions {
+        source_name: "dir/\"odd\" näme.tt".to_string(),
+        ..CompileOptions::default()
+    };
+    let c_code = compile(SOURCE, &options).unwrap().c_code;
+    assert!(c_code.contains("#line 3 \"dir/\\\"odd\\\" n\\303\\244me.tt\"\n"));
+}
+
+// Build SOURCE with the system C compiler and extra flags, or None without one.
+fn build_with(flags: &[&str]) -> Option<Vec<Diagnostic>> {
+    let mut toolchain = Toolchain::from_env();
+    if Command::new(&toolchain.cc)
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        return None;
+    }
+    toolchain
+        .flags
+        .extend(flags.iter().map(|flag| flag.to_string()));
+    let options = CompileOptions {
+        source_name: "h.tt".to_string(),
+        ..CompileOptions::default()
+    };
+    let output = compile(SOURCE, &options).unwrap();
+    let dir = scratch_dir().unwrap();
+    let result = toolchain.build(&output, "h.tt", SOURCE, &dir.join("program"));
+    fs::remove_dir_all(&dir).unwrap();
+    Some(result.unwrap_err())
+}
+
+#[test]
+fn maps_only_the_errors_the_c_compiler_reports_in_the_source() {
+    let dir = scratch_dir().unwrap();
+    let header = dir.join("bad.h");
+    fs::write(&header, "int x;\n\nint y = ;\n").unwrap();
+    let diagnostics = build_with(&["-include", header.to_str().unwrap()]);
+    fs::remove_dir_all(&dir).unwrap();
+    if let Some(diagnostics) = diagnostics {
+        assert!(diagnostics.iter().all(|d| d.span.is_none()));
+        assert!(diagnostics.iter().any(|d| d.message.contains("bad.h:3")));
+    }
+
+    // Making `b` a number breaks its assignment, on line 3.
+    if let Some(diagnostics) = build_with(&["-Dtt_b=1"]) {
+        assert!(lines(&diagnostics).contains(&Some(3)));
+    }
+}
+
+#[test]
+fn scratch_dirs_are_new_and_empty() {
+    let first = scratch_dir().unwrap();
+    let second = scratch_dir().unwrap();
+    assert_ne!(first, second);
+    assert_eq!(fs::read_dir(&first).unwrap().count(), 0);
+    fs::remove_dir(&first).unwrap();
+    fs::remove_dir(&second).unwrap();
+}